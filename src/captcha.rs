@@ -0,0 +1,59 @@
+use crate::login::LoginError;
+use crate::service::BoxFuture;
+use reqwest::Client;
+use std::future::Future;
+use std::path::Path;
+
+/// CAS 登录页下发的验证码图片
+#[derive(Debug, Clone)]
+pub struct CaptchaImage {
+    /// 图片内容
+    pub bytes: Vec<u8>,
+    /// 响应的 Content-Type，e.g. image/jpeg
+    pub content_type: Option<String>,
+}
+
+impl CaptchaImage {
+    /// 保存到文件，便于人工识别或交给外部程序
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, &self.bytes)
+    }
+}
+
+/// 识别验证码，可以是人工输入、保存到文件后等待输入，或调用外部识别服务。
+/// 识别是异步的，不会阻塞登录所在的运行时线程。
+pub trait CaptchaSolver: Send + Sync {
+    /// 返回验证码答案；返回 `None` 表示放弃登录
+    fn solve<'a>(&'a self, image: &'a CaptchaImage) -> BoxFuture<'a, Option<String>>;
+}
+
+/// 接收验证码图片、返回 future 的闭包，e.g. `|image| async move { ... }`
+impl<F, Fut> CaptchaSolver for F
+where
+    F: Fn(CaptchaImage) -> Fut + Send + Sync,
+    Fut: Future<Output = Option<String>> + Send + 'static,
+{
+    fn solve<'a>(&'a self, image: &'a CaptchaImage) -> BoxFuture<'a, Option<String>> {
+        Box::pin(self(image.clone()))
+    }
+}
+
+/// 下载验证码图片。必须使用登录时的同一个客户端，验证码与 cookie 中的会话绑定。
+pub(crate) async fn fetch(client: &Client, url: &str) -> Result<CaptchaImage, LoginError> {
+    let resp = client
+        .get(url)
+        .send()
+        .await
+        .map_err(LoginError::RequestError)?;
+    let content_type = resp
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let bytes = resp.bytes().await.map_err(LoginError::RequestError)?;
+    log::info!("Downloaded captcha from {url}, {} bytes", bytes.len());
+    Ok(CaptchaImage {
+        bytes: bytes.to_vec(),
+        content_type,
+    })
+}
@@ -0,0 +1,101 @@
+use crate::course::{Batch, CourseError, CourseInfo, CoursePage, CourseType};
+use crate::managed::ManagedCourseSession;
+use futures_util::future::ready;
+use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
+
+/// 遍历课程列表的参数
+#[derive(Debug, Clone)]
+pub struct CatalogConfig {
+    /// 每页的课程数
+    pub page_size: u32,
+    /// 同时请求的页数
+    pub concurrency: usize,
+}
+
+impl Default for CatalogConfig {
+    fn default() -> Self {
+        Self {
+            page_size: 50,
+            concurrency: 4,
+        }
+    }
+}
+
+/// 某个批次、课程类型和搜索关键词下的全部课程，自动翻页
+pub struct Catalog<'a> {
+    session: &'a ManagedCourseSession,
+    batch: &'a Batch,
+    course_type: CourseType,
+    query: String,
+    config: CatalogConfig,
+}
+
+impl<'a> Catalog<'a> {
+    pub fn new(
+        session: &'a ManagedCourseSession,
+        batch: &'a Batch,
+        course_type: CourseType,
+        query: impl Into<String>,
+    ) -> Self {
+        Self {
+            session,
+            batch,
+            course_type,
+            query: query.into(),
+            config: CatalogConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: CatalogConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// 按服务器返回的顺序逐个产出课程。先请求第一页得到课程总数，
+    /// 其余各页最多 `concurrency` 个同时请求。出错后流随即结束。
+    ///
+    /// 服务器可能限制每页的数量，所以之后按第一页实际返回的数量翻页；
+    /// 没有给出总数时一直请求到返回空页为止。
+    pub fn stream(&self) -> impl Stream<Item = Result<CourseInfo, CourseError>> + '_ {
+        let page_size = self.config.page_size.max(1);
+        let concurrency = self.config.concurrency.max(1);
+        stream::once(self.page(0, page_size))
+            .map_ok(move |first| {
+                let size = u32::try_from(first.courses.len())
+                    .unwrap_or(u32::MAX)
+                    .min(page_size);
+                log::debug!("Catalog has {:?} courses, {size} per page", first.total);
+                let rest = match first.total {
+                    _ if size == 0 => stream::empty().boxed(),
+                    Some(total) => stream::iter(1..total.div_ceil(size))
+                        .map(move |page| self.page(page, size))
+                        .buffered(concurrency)
+                        .boxed(),
+                    None => stream::iter(1..)
+                        .map(move |page| self.page(page, size))
+                        .buffered(concurrency)
+                        .try_take_while(|page| ready(Ok(!page.courses.is_empty())))
+                        .boxed(),
+                };
+                stream::once(ready(Ok(first))).chain(rest)
+            })
+            .try_flatten()
+            .map_ok(|page| stream::iter(page.courses.into_iter().map(Ok)))
+            .try_flatten()
+            .take_while({
+                let mut failed = false;
+                move |result| ready(!std::mem::replace(&mut failed, result.is_err()))
+            })
+    }
+
+    /// 取得全部课程
+    pub async fn fetch_all(&self) -> Result<Vec<CourseInfo>, CourseError> {
+        self.stream().try_collect().await
+    }
+
+    async fn page(&self, page: u32, page_size: u32) -> Result<CoursePage, CourseError> {
+        self.session
+            .list_course_page(self.batch, self.course_type, page, page_size, &self.query)
+            .await
+    }
+}
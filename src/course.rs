@@ -1,6 +1,133 @@
-use reqwest::Client;
+use crate::endpoints::Endpoints;
+use crate::login::{LoginError, LoginSuccess, truncate_string};
+use crate::timetable::{Schedule, parse_teaching_place};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use reqwest::{Client, RequestBuilder, Response, StatusCode, header};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// 选课系统各接口的路径，见 [`Endpoints::course_url`]
+const REGISTER_PATH: &str = "/xsxkapp/sys/xsxkapp/student/register.do";
+const BATCH_LIST_PATH: &str = "/xsxkapp/sys/xsxkapp/elective/batch.do";
+const PROGRAM_COURSE_PATH: &str = "/xsxkapp/sys/xsxkapp/elective/programCourse.do";
+const VOLUNTEER_PATH: &str = "/xsxkapp/sys/xsxkapp/elective/volunteer.do";
+const DELETE_VOLUNTEER_PATH: &str = "/xsxkapp/sys/xsxkapp/elective/deleteVolunteer.do";
+const CAPACITY_PATH: &str = "/xsxkapp/sys/xsxkapp/elective/teachingclass/capacity.do";
+
+/// 选课系统在 token 失效或未登录时返回的业务码
+const SESSION_EXPIRED_CODES: &[&str] = &["302", "401", "403"];
+
+/// 选课系统表示成功的业务码
+const SUCCESS_CODE: &str = "1";
+
+#[derive(Debug, Error)]
+pub enum CourseError {
+    #[error("HTTP request error: {0}")]
+    RequestError(reqwest::Error),
+    /// 响应不是 JSON，参数为地址和响应体的开头
+    #[error("Non-JSON response from {0}: {1}")]
+    NotJson(String, String),
+    /// 选课系统返回了表示失败的业务码，参数为业务码和提示信息
+    #[error("Server error {0}: {1}")]
+    ServerError(String, String),
+    #[error("Course session expired")]
+    SessionExpired,
+    /// 响应的结构与预期不符，参数为出错的字段路径和原因
+    #[error("Unexpected response schema at `{0}`: {1}")]
+    SchemaMismatch(String, String),
+    #[error("Re-login failed: {0}")]
+    ReloginFailed(LoginError),
+}
+
+impl CourseError {
+    /// 重试是否可能成功：网络错误、网关或维护页面、服务器繁忙等业务错误通常是暂时的。
+    /// 会话失效由 [`crate::managed::ManagedCourseSession`] 重新登录处理，不在此列。
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            CourseError::RequestError(_) | CourseError::NotJson(..) | CourseError::ServerError(..)
+        )
+    }
+}
+
+/// 响应中的业务码，可能是字符串或数字
+fn response_code(json: &Value) -> Option<String> {
+    match &json["code"] {
+        Value::String(code) => Some(code.clone()),
+        Value::Number(code) => Some(code.to_string()),
+        _ => None,
+    }
+}
+
+/// 响应中的提示信息
+fn response_message(json: &Value) -> String {
+    json["msg"]
+        .as_str()
+        .or_else(|| json["message"].as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// 解析 JSON 响应。没有跟随的重定向和认证失败的状态码、被重定向到 CAS 登录页，
+/// 以及表示未登录的业务码，都表示会话已失效。
+async fn parse_response(resp: Response) -> Result<Value, CourseError> {
+    let status = resp.status();
+    let url = resp.url().to_string();
+    if status.is_redirection()
+        || status == StatusCode::UNAUTHORIZED
+        || status == StatusCode::FORBIDDEN
+    {
+        log::info!("Course session expired: {status} on {url}");
+        return Err(CourseError::SessionExpired);
+    }
+    let body = resp.text().await.map_err(CourseError::RequestError)?;
+    let Ok(json) = serde_json::from_str::<Value>(&body) else {
+        if body.contains("/cas/login") {
+            log::info!("Course session expired: CAS login page from {url}");
+            return Err(CourseError::SessionExpired);
+        }
+        return Err(CourseError::NotJson(url, truncate_string(&body, 200)));
+    };
+    match response_code(&json) {
+        Some(code) if SESSION_EXPIRED_CODES.contains(&code.as_str()) => {
+            log::info!("Course session expired: {json}");
+            Err(CourseError::SessionExpired)
+        }
+        _ => Ok(json),
+    }
+}
+
+/// 业务码表示失败时返回 [`CourseError::ServerError`]，没有业务码的响应视为成功
+fn check_success(json: Value) -> Result<Value, CourseError> {
+    match response_code(&json) {
+        Some(code) if code != SUCCESS_CODE => {
+            Err(CourseError::ServerError(code, response_message(&json)))
+        }
+        _ => Ok(json),
+    }
+}
+
+/// 按字段路径（例如 `data.token`）取出并反序列化，出错时报告具体的字段路径
+fn field<T: DeserializeOwned>(json: &Value, path: &str) -> Result<T, CourseError> {
+    let value = path
+        .split('.')
+        .try_fold(json, |value, key| value.get(key))
+        .ok_or_else(|| {
+            CourseError::SchemaMismatch(path.to_string(), "missing field".to_string())
+        })?;
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let inner = e.path().to_string();
+        let path = if inner == "." {
+            path.to_string()
+        } else {
+            format!("{path}.{inner}")
+        };
+        CourseError::SchemaMismatch(path, e.into_inner().to_string())
+    })
+}
 
 /// 性别限制类型
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
@@ -26,6 +153,13 @@ impl Display for GenderLimit {
     }
 }
 
+/// 学生性别，用于判断分性别的课容量
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Gender {
+    Male,
+    Female,
+}
+
 /// 课程会话，用于管理用户登录状态和课程操作
 pub struct CourseSession {
     /// 学号
@@ -35,29 +169,140 @@ pub struct CourseSession {
     /// 客户端
     pub client: Client,
     token: String,
+    /// 是否经由 WebVPN 访问
+    webvpn: bool,
+    /// 选课系统和 WebVPN 网关的地址
+    endpoints: Endpoints,
 }
 
 impl CourseSession {
-    pub async fn fron_client(client: Client) -> Option<Self> {
-        let json = client
-            .get("https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/student/register.do")
-            .send()
+    pub async fn fron_client(client: Client) -> Result<Self, CourseError> {
+        Self::from_client_with_route(client, false, Endpoints::default()).await
+    }
+
+    /// 从登录结果创建会话，沿用登录时选择的访问方式
+    pub async fn from_login(login: &LoginSuccess) -> Result<Self, CourseError> {
+        Self::from_client_with_route(login.client.clone(), login.webvpn, login.endpoints.clone())
             .await
-            .ok()?
-            .json::<serde_json::Value>()
+    }
+
+    /// 用保存的学号、姓名和 token 重建会话，不请求 register.do
+    pub(crate) fn from_saved(
+        client: Client,
+        number: String,
+        name: String,
+        token: String,
+        webvpn: bool,
+        endpoints: Endpoints,
+    ) -> Self {
+        Self {
+            number,
+            name,
+            client,
+            token,
+            webvpn,
+            endpoints,
+        }
+    }
+
+    pub(crate) fn token(&self) -> &str {
+        &self.token
+    }
+
+    async fn from_client_with_route(
+        client: Client,
+        webvpn: bool,
+        endpoints: Endpoints,
+    ) -> Result<Self, CourseError> {
+        let resp = client
+            .get(endpoints.route(&endpoints.course_url(REGISTER_PATH), webvpn))
+            .send()
             .await
-            .ok()?;
-        Some(Self {
-            number: json["data"]["number"].as_str()?.to_string(),
-            name: json["data"]["name"].as_str()?.to_string(),
-            token: json["data"]["token"].as_str()?.to_string(),
+            .map_err(CourseError::RequestError)?;
+        let json = check_success(parse_response(resp).await?)?;
+        Ok(Self {
+            number: field(&json, "data.number")?,
+            name: field(&json, "data.name")?,
+            token: field(&json, "data.token")?,
             client,
+            webvpn,
+            endpoints,
         })
     }
+
+    /// 选课系统接口的完整 URL，按会话的访问方式改写
+    fn url(&self, path: &str) -> String {
+        self.endpoints
+            .route(&self.endpoints.course_url(path), self.webvpn)
+    }
+
+    /// 用一次轻量请求检查会话是否仍然有效
+    pub async fn is_alive(&self) -> bool {
+        self.get_batch_list().await.is_ok()
+    }
+
+    /// 携带 token 发送请求
+    async fn send(&self, req: RequestBuilder) -> Result<Response, CourseError> {
+        req.header("X-Requested-With", "XMLHttpRequest")
+            .header("token", &self.token)
+            .send()
+            .await
+            .map_err(CourseError::RequestError)
+    }
+
+    /// 携带 token 发送请求并解析 JSON 响应，不检查业务码，见 [`parse_response`]
+    async fn send_raw(&self, req: RequestBuilder) -> Result<Value, CourseError> {
+        parse_response(self.send(req).await?).await
+    }
+
+    /// 同 [`Self::send_raw`]，业务码表示失败时返回 [`CourseError::ServerError`]
+    async fn send_json(&self, req: RequestBuilder) -> Result<Value, CourseError> {
+        self.send_raw(req).await.and_then(check_success)
+    }
+
+    /// 获取选课批次
+    pub async fn get_batch_list(&self) -> Result<Vec<Batch>, CourseError> {
+        let resp = self
+            .send_json(self.client.get(self.url(BATCH_LIST_PATH)))
+            .await?;
+        field(&resp, "dataList")
+    }
+
+    /// 请求一次批次列表，返回响应头 `Date` 中的服务器时间（精确到秒），用于估计时钟偏差
+    pub async fn server_date(&self) -> Result<DateTime<Utc>, CourseError> {
+        let resp = self
+            .send(self.client.get(self.url(BATCH_LIST_PATH)))
+            .await?;
+        let date = resp
+            .headers()
+            .get(header::DATE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        check_success(parse_response(resp).await?)?;
+        let date = date.ok_or_else(|| {
+            CourseError::SchemaMismatch("Date".to_string(), "missing header".to_string())
+        })?;
+        DateTime::parse_from_rfc2822(&date)
+            .map(|date| date.with_timezone(&Utc))
+            .map_err(|e| CourseError::SchemaMismatch("Date".to_string(), format!("{e}: {date:?}")))
+    }
+}
+
+/// 选课系统中的时间均为北京时间
+const SERVER_UTC_OFFSET: i32 = 8 * 3600;
+
+/// 解析选课系统中 `2025-01-01 08:00:00` 格式的时间
+pub fn parse_server_time(time: &str) -> Result<DateTime<FixedOffset>, chrono::ParseError> {
+    let offset = FixedOffset::east_opt(SERVER_UTC_OFFSET).expect("valid offset");
+    NaiveDateTime::parse_from_str(time.trim(), "%Y-%m-%d %H:%M:%S").map(|time| {
+        time.and_local_timezone(offset)
+            .single()
+            .expect("fixed offset")
+    })
 }
 
 /// 选课批次信息
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Batch {
     #[serde(rename = "batchType")]
     pub batch_type: String,
@@ -88,22 +333,37 @@ pub struct Batch {
     pub week_range: String,
 }
 
-/// 获取选课批次
-pub async fn get_batch_list(client: &Client) -> Result<Vec<Batch>, reqwest::Error> {
+impl Batch {
+    /// 选课开始时间
+    pub fn begins_at(&self) -> Result<DateTime<FixedOffset>, CourseError> {
+        parse_server_time(&self.begin_time)
+            .map_err(|e| CourseError::SchemaMismatch("beginTime".to_string(), e.to_string()))
+    }
+
+    /// 选课结束时间
+    pub fn ends_at(&self) -> Result<DateTime<FixedOffset>, CourseError> {
+        parse_server_time(&self.end_time)
+            .map_err(|e| CourseError::SchemaMismatch("endTime".to_string(), e.to_string()))
+    }
+}
+
+/// 使用已登录的客户端获取选课批次，`webvpn` 为 `true` 时经由 `endpoints` 中的网关访问
+pub async fn get_batch_list(
+    client: &Client,
+    endpoints: &Endpoints,
+    webvpn: bool,
+) -> Result<Vec<Batch>, CourseError> {
     let resp = client
-        .get("https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/elective/batch.do")
+        .get(endpoints.route(&endpoints.course_url(BATCH_LIST_PATH), webvpn))
         .send()
-        .await?
-        .json::<serde_json::Value>()
-        .await?;
-
-    let batches = resp["dataList"].clone();
-    let batches: Vec<Batch> = serde_json::from_value(batches).unwrap_or_default();
-    Ok(batches)
+        .await
+        .map_err(CourseError::RequestError)?;
+    field(&check_success(parse_response(resp).await?)?, "dataList")
 }
 
 /// 选课类型，按照网页顺序
-#[derive(Deserialize, Serialize, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum CourseType {
     /// 主修推荐课程
     TJKC,
@@ -124,7 +384,7 @@ pub enum CourseType {
 }
 
 /// 课程基本信息
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct CourseInfo {
     /// 院系名称
     #[serde(rename = "departmentName")]
@@ -158,8 +418,16 @@ pub struct CourseInfo {
     pub major_flag: String,
 }
 
+/// 课程列表中的一页
+#[derive(Serialize)]
+pub struct CoursePage {
+    /// 符合条件的课程总数，服务器没有给出时为 `None`
+    pub total: Option<u32>,
+    pub courses: Vec<CourseInfo>,
+}
+
 /// 教学班信息
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct TeachingClass {
     /// 课程号
     #[serde(rename = "courseNumber")]
@@ -196,6 +464,13 @@ pub struct TeachingClass {
     pub is_conflict: bool,
 }
 
+impl TeachingClass {
+    /// 解析 [`Self::teaching_place`] 得到的上课安排
+    pub fn schedule(&self) -> Schedule {
+        parse_teaching_place(&self.teaching_place)
+    }
+}
+
 /// 自定义反序列化函数：将字符串"0"/"1"转换为布尔值
 fn deserialize_bool_from_string<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
@@ -220,7 +495,23 @@ impl CourseSession {
         course_type: CourseType,
         page: u32,
         query: &str,
-    ) -> Vec<CourseInfo> {
+    ) -> Result<Vec<CourseInfo>, CourseError> {
+        Ok(self
+            .list_course_page(batch, course_type, page, 10, query)
+            .await?
+            .courses)
+    }
+
+    /// 按指定的每页数量列出课程，同时返回符合条件的课程总数。
+    /// 遍历所有页见 [`crate::catalog::Catalog`]。
+    pub async fn list_course_page(
+        &self,
+        batch: &Batch,
+        course_type: CourseType,
+        page: u32,
+        page_size: u32,
+        query: &str,
+    ) -> Result<CoursePage, CourseError> {
         let params = serde_json::json!({
             "data": {
                 "studentCode": self.number,
@@ -232,58 +523,43 @@ impl CourseSession {
                 "checkCapacity": "2",
                 "queryContent": query
             },
-            "pageSize": "10",
+            "pageSize": format!("{}", page_size),
             "pageNumber": format!("{}", page),
             "order": ""
         });
-        let resp = self
+        let req = self
             .client
-            .post("https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/elective/programCourse.do")
+            .post(self.url(PROGRAM_COURSE_PATH))
             .header(
                 "Content-Type",
                 "application/x-www-form-urlencoded; charset=UTF-8",
             )
-            .header("X-Requested-With", "XMLHttpRequest")
-            .header("token", &self.token)
             .body(format!(
                 "querySetting={}",
                 urlencoding::encode(&params.to_string())
-            ))
-            .send()
-            .await
-            .unwrap()
-            .json::<serde_json::Value>()
-            .await
-            .unwrap();
-        let courses = resp["dataList"].clone();
-        let courses: Vec<CourseInfo> = serde_json::from_value(courses).unwrap();
-        for course in &courses {
-            println!(
-                "{} - {} - {}",
-                course.course_number,
-                course.course_name,
-                if course.selected { "已选" } else { "未选" }
-            );
-            for tc in &course.tc_list {
-                println!(
-                    "  {} - {} - {} - {}/{} - {}",
-                    tc.teaching_class_id,
-                    tc.teacher_name,
-                    tc.teaching_place,
-                    tc.number_of_selected,
-                    tc.class_capacity,
-                    if tc.is_choose { "已选" } else { "未选" }
-                );
-            }
-        }
-        courses
+            ));
+        let resp = self.send_json(req).await?;
+        let listed = CoursePage {
+            total: field(&resp, "totalCount").ok(),
+            courses: field(&resp, "dataList")?,
+        };
+        log::debug!(
+            "Listed {} of {:?} courses on page {page}",
+            listed.courses.len(),
+            listed.total
+        );
+        Ok(listed)
     }
 
     /// 取消选课志愿
     /// # Arguments
     /// * `batch` - 选课批次信息
     /// * `class_id` - 教学班ID
-    pub async fn delete_volunteer(&self, batch: &Batch, class_id: &str) {
+    pub async fn delete_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+    ) -> Result<VolunteerOutcome, CourseError> {
         let params = serde_json::json!({
             "data": {
                 "operationType": "2",
@@ -293,19 +569,13 @@ impl CourseSession {
                 "isMajor": "1"
             }
         });
-        let resp = self
+        let req = self
             .client
-            .get("https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/elective/deleteVolunteer.do")
-            .query(&[("deleteParam", params.to_string())])
-            .header("X-Requested-With", "XMLHttpRequest")
-            .header("token", &self.token)
-            .send()
-            .await
-            .unwrap()
-            .json::<serde_json::Value>()
-            .await
-            .unwrap();
-        println!("{resp}");
+            .get(self.url(DELETE_VOLUNTEER_PATH))
+            .query(&[("deleteParam", params.to_string())]);
+        let resp = self.send_raw(req).await?;
+        log::debug!("Volunteer response for {class_id}: {resp}");
+        Ok(VolunteerOutcome::parse(&resp))
     }
 
     /// 添加选课志愿
@@ -313,7 +583,12 @@ impl CourseSession {
     /// * `batch` - 选课批次信息
     /// * `class_id` - 教学班ID
     /// * `course_type` - 课程类型
-    pub async fn add_volunteer(&self, batch: &Batch, class_id: &str, course_type: CourseType) {
+    pub async fn add_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+        course_type: CourseType,
+    ) -> Result<VolunteerOutcome, CourseError> {
         let params = serde_json::json!({
             "data": {
                 "operationType": "1",
@@ -325,9 +600,9 @@ impl CourseSession {
                 "teachingClassType": format!("{:?}", course_type)
             }
         });
-        let resp = self
+        let req = self
             .client
-            .post("https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/elective/volunteer.do")
+            .post(self.url(VOLUNTEER_PATH))
             .header(
                 "Content-Type",
                 "application/x-www-form-urlencoded; charset=UTF-8",
@@ -335,16 +610,10 @@ impl CourseSession {
             .body(format!(
                 "addParam={}",
                 urlencoding::encode(&params.to_string())
-            ))
-            .header("X-Requested-With", "XMLHttpRequest")
-            .header("token", &self.token)
-            .send()
-            .await
-            .unwrap()
-            .json::<serde_json::Value>()
-            .await
-            .unwrap();
-        println!("{resp}");
+            ));
+        let resp = self.send_raw(req).await?;
+        log::debug!("Volunteer response for {class_id}: {resp}");
+        Ok(VolunteerOutcome::parse(&resp))
     }
 
     /// 获取教学班容量详细信息
@@ -352,46 +621,152 @@ impl CourseSession {
     /// * `class_id` - 教学班ID
     /// # Returns
     /// 返回容量信息结构体
-    pub async fn get_capacity(&self, class_id: &str) -> CapacityInfo {
-        let resp = self
+    pub async fn get_capacity(&self, class_id: &str) -> Result<CapacityInfo, CourseError> {
+        let req = self
             .client
-            .get("https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/elective/teachingclass/capacity.do")
-            .query(&[("teachingClassId", class_id), ("capacitySuffix", "")])
-            .header("X-Requested-With", "XMLHttpRequest")
-            .header("token", &self.token)
-            .send()
-            .await
-            .unwrap()
-            .json::<serde_json::Value>()
-            .await
-            .unwrap();
-        macro_rules! get_int_field {
-            ($field:expr) => {
-                resp["data"][$field]
-                    .as_str()
-                    .unwrap()
-                    .parse::<u32>()
-                    .unwrap()
-            };
-        }
-        let number_of_male = get_int_field!("numberOfMale");
-        let capacity_of_male = get_int_field!("capacityOfMale");
-        let number_of_female = get_int_field!("numberOfFemale");
-        let capacity_of_female = get_int_field!("capacityOfFemale");
-        let number_of_selected = get_int_field!("numberOfSelected");
-        let class_capacity = get_int_field!("classCapacity");
-        CapacityInfo {
+            .get(self.url(CAPACITY_PATH))
+            .query(&[("teachingClassId", class_id), ("capacitySuffix", "")]);
+        let resp = self.send_json(req).await?;
+        // 人数和容量都是字符串形式的整数
+        let count = |name: &str| {
+            let path = format!("data.{name}");
+            let value: String = field(&resp, &path)?;
+            value
+                .parse::<u32>()
+                .map_err(|e| CourseError::SchemaMismatch(path, format!("{e}: {value:?}")))
+        };
+        let number_of_male = count("numberOfMale")?;
+        let capacity_of_male = count("capacityOfMale")?;
+        let number_of_female = count("numberOfFemale")?;
+        let capacity_of_female = count("capacityOfFemale")?;
+        let number_of_selected = count("numberOfSelected")?;
+        let class_capacity = count("classCapacity")?;
+        Ok(CapacityInfo {
             number_of_male,
             capacity_of_male,
             number_of_female,
             capacity_of_female,
             number_of_selected,
             class_capacity,
+        })
+    }
+
+    /// 从教学班 `from` 换到 `to`。先确认 `to` 有空位（设置 `gender` 时同时检查分性别名额），
+    /// 然后紧接着退选 `from`、选 `to`；没有选上 `to` 时立即重新选回 `from`。
+    ///
+    /// 在退选之前出错时返回 `Err`，此时原教学班未受影响（退选请求本身出错时结果未知）；
+    /// 退选之后的失败记录在 [`SwapOutcome`] 中。例外是退选之后会话失效：这时无法选回原教学班，
+    /// 返回 [`CourseError::SessionExpired`]，原教学班已经退选。需要重新登录后继续回滚时使用
+    /// [`crate::managed::ManagedCourseSession::swap_class`]。
+    pub async fn swap_class(
+        &self,
+        batch: &Batch,
+        from: &str,
+        to: &str,
+        course_type: CourseType,
+        gender: Option<Gender>,
+    ) -> Result<SwapOutcome, CourseError> {
+        swap(self, batch, from, to, course_type, gender).await
+    }
+}
+
+/// 换课的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum SwapOutcome {
+    /// 已换到新教学班
+    Swapped,
+    /// 新教学班没有空位，未做任何操作
+    TargetFull,
+    /// 退选原教学班失败，未做其他操作
+    DropFailed(VolunteerOutcome),
+    /// 没有选上新教学班，已选回原教学班，参数为选新教学班的结果
+    RolledBack(VolunteerOutcome),
+    /// 新旧教学班都没有选上，参数为选新教学班和选回原教学班的结果
+    Lost(VolunteerOutcome, VolunteerOutcome),
+}
+
+impl Display for SwapOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwapOutcome::Swapped => write!(f, "换课成功"),
+            SwapOutcome::TargetFull => write!(f, "目标教学班已满，未换课"),
+            SwapOutcome::DropFailed(outcome) => write!(f, "退选原教学班失败：{outcome}"),
+            SwapOutcome::RolledBack(outcome) => {
+                write!(f, "选课失败（{outcome}），已选回原教学班")
+            }
+            SwapOutcome::Lost(added, restored) => {
+                write!(f, "选课失败（{added}），选回原教学班也失败（{restored}）")
+            }
+        }
+    }
+}
+
+/// 添加或取消选课志愿的结果，由选课系统返回的业务码和提示信息得出
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum VolunteerOutcome {
+    /// 操作成功
+    Success,
+    /// 教学班已满
+    Full,
+    /// 与已选课程时间冲突
+    Conflict,
+    /// 已选过该课程或该教学班
+    AlreadySelected,
+    /// 当前不在选课时间内
+    NotInPeriod,
+    /// 超出学分上限
+    CreditLimitExceeded,
+    /// 无法识别的结果，参数为服务器的原始提示信息
+    Unknown(String),
+}
+
+impl VolunteerOutcome {
+    /// 按提示信息中的关键词分类。顺序有意义："已选人数已满" 应当归为已满。
+    fn parse(json: &Value) -> Self {
+        if response_code(json).as_deref() == Some(SUCCESS_CODE) {
+            return VolunteerOutcome::Success;
+        }
+        let message = response_message(json);
+        let has = |keywords: &[&str]| keywords.iter().any(|k| message.contains(k));
+        if has(&["已满", "满员", "无余量", "容量不足"]) {
+            VolunteerOutcome::Full
+        } else if has(&["冲突"]) {
+            VolunteerOutcome::Conflict
+        } else if has(&["学分"]) {
+            VolunteerOutcome::CreditLimitExceeded
+        } else if has(&["不在选课时间", "未开放", "未开始", "已结束", "不在时间"])
+        {
+            VolunteerOutcome::NotInPeriod
+        } else if has(&["已选", "重复"]) {
+            VolunteerOutcome::AlreadySelected
+        } else if message.is_empty() {
+            VolunteerOutcome::Unknown(json.to_string())
+        } else {
+            VolunteerOutcome::Unknown(message)
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        *self == VolunteerOutcome::Success
+    }
+}
+
+impl Display for VolunteerOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VolunteerOutcome::Success => write!(f, "成功"),
+            VolunteerOutcome::Full => write!(f, "教学班已满"),
+            VolunteerOutcome::Conflict => write!(f, "时间冲突"),
+            VolunteerOutcome::AlreadySelected => write!(f, "已选过该课程"),
+            VolunteerOutcome::NotInPeriod => write!(f, "不在选课时间内"),
+            VolunteerOutcome::CreditLimitExceeded => write!(f, "超出学分上限"),
+            VolunteerOutcome::Unknown(message) => write!(f, "未知结果：{message}"),
         }
     }
 }
 
 /// 教学班容量详细信息
+#[derive(Debug, Serialize)]
 pub struct CapacityInfo {
     /// 已选男生人数
     pub number_of_male: u32,
@@ -407,6 +782,22 @@ pub struct CapacityInfo {
     pub class_capacity: u32,
 }
 
+impl CapacityInfo {
+    /// 是否还有空位。分性别设置了容量的教学班，还要求 `gender` 对应的名额未满；
+    /// `gender` 为 `None` 时只看总容量。
+    pub fn has_seat(&self, gender: Option<Gender>) -> bool {
+        if self.number_of_selected >= self.class_capacity {
+            return false;
+        }
+        let split = self.capacity_of_male + self.capacity_of_female > 0;
+        match gender {
+            Some(Gender::Male) if split => self.number_of_male < self.capacity_of_male,
+            Some(Gender::Female) if split => self.number_of_female < self.capacity_of_female,
+            _ => true,
+        }
+    }
+}
+
 impl Display for CapacityInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -421,3 +812,81 @@ impl Display for CapacityInfo {
         )
     }
 }
+
+/// 换教学班用到的操作。普通会话直接请求，[`crate::managed::ManagedCourseSession`]
+/// 在每一步会话失效时重新登录后重试，所以退选之后 token 过期也能继续选课或回滚。
+pub(crate) trait SwapSteps {
+    async fn get_capacity(&self, class_id: &str) -> Result<CapacityInfo, CourseError>;
+    async fn delete_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+    ) -> Result<VolunteerOutcome, CourseError>;
+    async fn add_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+        course_type: CourseType,
+    ) -> Result<VolunteerOutcome, CourseError>;
+}
+
+impl SwapSteps for CourseSession {
+    async fn get_capacity(&self, class_id: &str) -> Result<CapacityInfo, CourseError> {
+        CourseSession::get_capacity(self, class_id).await
+    }
+
+    async fn delete_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+    ) -> Result<VolunteerOutcome, CourseError> {
+        CourseSession::delete_volunteer(self, batch, class_id).await
+    }
+
+    async fn add_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+        course_type: CourseType,
+    ) -> Result<VolunteerOutcome, CourseError> {
+        CourseSession::add_volunteer(self, batch, class_id, course_type).await
+    }
+}
+
+/// 见 [`CourseSession::swap_class`]
+pub(crate) async fn swap(
+    session: &impl SwapSteps,
+    batch: &Batch,
+    from: &str,
+    to: &str,
+    course_type: CourseType,
+    gender: Option<Gender>,
+) -> Result<SwapOutcome, CourseError> {
+    let capacity = session.get_capacity(to).await?;
+    if !capacity.has_seat(gender) {
+        return Ok(SwapOutcome::TargetFull);
+    }
+    let dropped = session.delete_volunteer(batch, from).await?;
+    if !dropped.is_success() {
+        return Ok(SwapOutcome::DropFailed(dropped));
+    }
+    // 会话失效时回滚也必然失败，交给调用方处理
+    let attempt =
+        async |class_id: &str| match session.add_volunteer(batch, class_id, course_type).await {
+            Err(CourseError::SessionExpired) => Err(CourseError::SessionExpired),
+            result => Ok(result.unwrap_or_else(|e| VolunteerOutcome::Unknown(e.to_string()))),
+        };
+    let added = attempt(to).await?;
+    if added.is_success() {
+        log::info!("Swapped {from} for {to}");
+        return Ok(SwapOutcome::Swapped);
+    }
+    log::warn!("Failed to add {to} ({added}), re-adding {from}");
+    let restored = attempt(from).await?;
+    if restored.is_success() {
+        Ok(SwapOutcome::RolledBack(added))
+    } else {
+        log::error!("Lost {from} while swapping for {to}: {restored}");
+        Ok(SwapOutcome::Lost(added, restored))
+    }
+}
@@ -0,0 +1,73 @@
+use crate::webvpn;
+use serde::{Deserialize, Serialize};
+
+/// 各服务的地址（协议 + 主机，不带结尾的 `/`）。
+/// 默认指向学校的正式服务；测试时可以全部指向本地的模拟服务器，学校迁移主机时也只需修改这里。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Endpoints {
+    /// CAS 认证中心
+    pub cas: String,
+    /// 选课系统
+    pub course: String,
+    /// AI 平台
+    pub ai: String,
+    /// WebVPN 网关
+    pub webvpn: String,
+    /// CAS 登录页加密密码所用的 RSA 公钥（PEM）
+    pub cas_public_key: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            cas: "https://login.xjtu.edu.cn".to_string(),
+            course: "https://xkfw.xjtu.edu.cn".to_string(),
+            ai: "https://ai.xjtu.edu.cn".to_string(),
+            webvpn: webvpn::WEBVPN_ORIGIN.to_string(),
+            cas_public_key: include_str!("XJTU_PublicKey").to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// 所有服务都位于同一个地址，例如 `http://127.0.0.1:8080` 上的模拟服务器。公钥沿用默认值。
+    pub fn with_base(base: &str) -> Self {
+        let base = base.trim_end_matches('/').to_string();
+        Self {
+            cas: base.clone(),
+            course: base.clone(),
+            ai: base.clone(),
+            webvpn: base,
+            ..Default::default()
+        }
+    }
+
+    /// CAS 上的地址，`path` 以 `/` 开头
+    pub fn cas_url(&self, path: &str) -> String {
+        format!("{}{path}", self.cas)
+    }
+
+    /// 选课系统上的地址，`path` 以 `/` 开头
+    pub fn course_url(&self, path: &str) -> String {
+        format!("{}{path}", self.course)
+    }
+
+    /// AI 平台上的地址，`path` 以 `/` 开头
+    pub fn ai_url(&self, path: &str) -> String {
+        format!("{}{path}", self.ai)
+    }
+
+    /// WebVPN 网关上的地址，`path` 以 `/` 开头
+    pub fn webvpn_url(&self, path: &str) -> String {
+        format!("{}{path}", self.webvpn)
+    }
+
+    /// 按访问方式选择实际请求的 URL，经由 WebVPN 时改写为本配置的网关地址
+    pub fn route(&self, url: &str, via_webvpn: bool) -> String {
+        if via_webvpn {
+            webvpn::rewrite_url_with_origin(&self.webvpn, url)
+        } else {
+            url.to_string()
+        }
+    }
+}
@@ -0,0 +1,526 @@
+use crate::endpoints::Endpoints;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// 脱敏后的占位值
+const REDACTED: &str = "REDACTED";
+
+/// 这些标记之后直到分隔符为止的内容视为凭据：加密后的密码、服务票据、选课 token
+const SECRET_MARKERS: &[&str] = &["__RSA__", "ticket=", r#""token":""#, r#""token": ""#];
+
+/// 不转发、不回放的逐跳首部。请求不接受压缩，便于改写和保存响应体。
+const SKIPPED_HEADERS: &[HeaderName] = &[
+    header::HOST,
+    header::CONNECTION,
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::ACCEPT_ENCODING,
+    header::CONTENT_ENCODING,
+];
+
+#[derive(Debug, Error)]
+pub enum FixtureError {
+    #[error("Fixture file I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Fixture file format error: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// 录制和回放时，每个上游服务占用一个本地端口
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Host {
+    Cas,
+    Course,
+    Ai,
+    WebVpn,
+}
+
+const HOSTS: [Host; 4] = [Host::Cas, Host::Course, Host::Ai, Host::WebVpn];
+
+impl Host {
+    fn origin(self, endpoints: &Endpoints) -> &str {
+        match self {
+            Host::Cas => &endpoints.cas,
+            Host::Course => &endpoints.course,
+            Host::Ai => &endpoints.ai,
+            Host::WebVpn => &endpoints.webvpn,
+        }
+    }
+
+    /// 夹具中代替服务地址的占位符，回放时替换为回放服务器的地址
+    fn placeholder(self) -> &'static str {
+        match self {
+            Host::Cas => "{{cas}}",
+            Host::Course => "{{course}}",
+            Host::Ai => "{{ai}}",
+            Host::WebVpn => "{{webvpn}}",
+        }
+    }
+}
+
+/// 一次 HTTP 往返
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub host: Host,
+    pub method: String,
+    /// 路径和查询参数
+    pub uri: String,
+    /// 请求体，仅用于查看，回放时不参与匹配
+    pub request_body: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// `body` 为 base64 编码的二进制内容，例如验证码图片
+    #[serde(default)]
+    pub binary: bool,
+}
+
+impl Exchange {
+    fn path(&self) -> &str {
+        self.uri.split('?').next().unwrap_or_default()
+    }
+}
+
+/// 录制下来的一组往返，按发生顺序排列。其中的凭据和 token 已脱敏，服务地址已替换为占位符。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Fixture {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Fixture {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FixtureError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// 将文本中出现的地址（包括 URL 编码后的形式）逐一替换。较长的地址先替换，
+/// 避免 `http://127.0.0.1:4100` 误伤 `http://127.0.0.1:41000`。
+fn replace_origins(text: &str, pairs: &[(&str, &str)]) -> String {
+    let mut pairs = pairs.to_vec();
+    pairs.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+    let mut text = text.to_string();
+    for (from, to) in pairs {
+        text = text
+            .replace(from, to)
+            .replace(&*urlencoding::encode(from), &urlencoding::encode(to));
+    }
+    text
+}
+
+/// 去掉凭据：调用方给出的明文（用户名、密码等）以及 [`SECRET_MARKERS`] 之后的值
+fn redact(text: &str, secrets: &[String]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        text = text
+            .replace(secret, REDACTED)
+            .replace(&*urlencoding::encode(secret), REDACTED);
+    }
+    for marker in SECRET_MARKERS {
+        let mut redacted = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(pos) = rest.find(marker) {
+            let (head, tail) = rest.split_at(pos + marker.len());
+            redacted.push_str(head);
+            let end = tail
+                .find(|c: char| c.is_whitespace() || "&\"'<>;".contains(c))
+                .unwrap_or(tail.len());
+            if end > 0 {
+                redacted.push_str(REDACTED);
+            }
+            rest = &tail[end..];
+        }
+        redacted.push_str(rest);
+        text = redacted;
+    }
+    text
+}
+
+/// Cookie 的值一律脱敏，只保留名称和属性
+fn redact_set_cookie(value: &str) -> String {
+    match value.split_once('=') {
+        Some((name, rest)) => {
+            let attributes = rest.find(';').map(|i| &rest[i..]).unwrap_or_default();
+            format!("{name}={REDACTED}{attributes}")
+        }
+        None => value.to_string(),
+    }
+}
+
+/// 本地服务器使用明文 HTTP，且所有服务都在 127.0.0.1 上，因此去掉 Domain 和 Secure 属性
+fn localize_set_cookie(value: &str) -> String {
+    value
+        .split(';')
+        .filter(|attr| {
+            let name = attr.trim().split('=').next().unwrap_or_default();
+            !name.eq_ignore_ascii_case("domain") && !name.eq_ignore_ascii_case("secure")
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 为每个上游服务启动一个本地服务器，返回各自的地址
+async fn serve_hosts(
+    router: impl Fn(Host) -> Router,
+) -> std::io::Result<(Vec<String>, Vec<JoinHandle<()>>)> {
+    let mut bases = Vec::new();
+    let mut handles = Vec::new();
+    for host in HOSTS {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        bases.push(format!("http://{}", listener.local_addr()?));
+        let app = router(host);
+        handles.push(tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                log::warn!("Fixture server stopped: {e}");
+            }
+        }));
+    }
+    Ok((bases, handles))
+}
+
+fn local_endpoints(bases: &[String], cas_public_key: String) -> Endpoints {
+    Endpoints {
+        cas: bases[0].clone(),
+        course: bases[1].clone(),
+        ai: bases[2].clone(),
+        webvpn: bases[3].clone(),
+        cas_public_key,
+    }
+}
+
+struct RecorderShared {
+    upstream: Endpoints,
+    local: Endpoints,
+    client: Client,
+    secrets: Vec<String>,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl RecorderShared {
+    /// (本地地址, 上游地址)
+    fn local_to_upstream(&self) -> Vec<(&str, &str)> {
+        HOSTS
+            .iter()
+            .map(|h| (h.origin(&self.local), h.origin(&self.upstream)))
+            .collect()
+    }
+
+    fn upstream_to(&self, to: impl Fn(Host) -> String) -> Vec<(String, String)> {
+        HOSTS
+            .iter()
+            .map(|h| (h.origin(&self.upstream).to_string(), to(*h)))
+            .collect()
+    }
+
+    fn redact(&self, text: &str) -> String {
+        redact(text, &self.secrets)
+    }
+}
+
+fn as_pairs(pairs: &[(String, String)]) -> Vec<(&str, &str)> {
+    pairs
+        .iter()
+        .map(|(a, b)| (a.as_str(), b.as_str()))
+        .collect()
+}
+
+/// 录制服务器：把请求转发给真实服务，把响应返回给客户端并记录下来。
+/// 客户端使用 [`Recorder::endpoints`] 即可，响应中的重定向地址和 cookie 会被改写为指向本地。
+pub struct Recorder {
+    shared: Arc<RecorderShared>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Recorder {
+    /// # Arguments
+    /// * `upstream` - 被录制的服务，通常为 [`Endpoints::default`]
+    /// * `secrets` - 需要从夹具中去掉的明文，例如用户名和密码
+    pub async fn start(upstream: Endpoints, secrets: Vec<String>) -> std::io::Result<Self> {
+        let client = Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .build()
+            .map_err(std::io::Error::other)?;
+        let state = Arc::new(Mutex::new(None::<Arc<RecorderShared>>));
+        let (bases, handles) = serve_hosts(|host| {
+            Router::new()
+                .fallback(record)
+                .with_state((host, state.clone()))
+        })
+        .await?;
+        let shared = Arc::new(RecorderShared {
+            local: local_endpoints(&bases, upstream.cas_public_key.clone()),
+            upstream,
+            client,
+            secrets,
+            exchanges: Mutex::new(Vec::new()),
+        });
+        *lock(&state) = Some(shared.clone());
+        Ok(Self { shared, handles })
+    }
+
+    /// 指向录制服务器的地址配置，公钥沿用上游的公钥
+    pub fn endpoints(&self) -> Endpoints {
+        self.shared.local.clone()
+    }
+
+    /// 到目前为止录制的往返
+    pub fn fixture(&self) -> Fixture {
+        Fixture {
+            exchanges: lock(&self.shared.exchanges).clone(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FixtureError> {
+        self.fixture().save(path)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.handles.iter().for_each(JoinHandle::abort);
+    }
+}
+
+type RecorderState = (Host, Arc<Mutex<Option<Arc<RecorderShared>>>>);
+
+async fn record(State((host, state)): State<RecorderState>, req: Request) -> Response {
+    let Some(shared) = lock(&state).clone() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+    match forward(&shared, host, req).await {
+        Ok(response) => response,
+        Err(e) => {
+            log::warn!("Failed to forward request: {e}");
+            (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+        }
+    }
+}
+
+async fn forward(
+    shared: &RecorderShared,
+    host: Host,
+    req: Request,
+) -> Result<Response, reqwest::Error> {
+    let to_upstream = shared.local_to_upstream();
+    let method = req.method().clone();
+    let uri = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/")
+        .to_string();
+    let mut upstream_req = shared.client.request(
+        method.clone(),
+        format!(
+            "{}{}",
+            host.origin(&shared.upstream),
+            replace_origins(&uri, &to_upstream)
+        ),
+    );
+    for (name, value) in req.headers() {
+        if SKIPPED_HEADERS.contains(name) {
+            continue;
+        }
+        let value = replace_origins(value.to_str().unwrap_or_default(), &to_upstream);
+        upstream_req = upstream_req.header(name, value);
+    }
+    let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+        .await
+        .unwrap_or_default();
+    let body = String::from_utf8_lossy(&body).into_owned();
+    let resp = upstream_req
+        .body(replace_origins(&body, &to_upstream))
+        .send()
+        .await?;
+
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let bytes = resp.bytes().await?;
+    let binary = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("image/"))
+        || std::str::from_utf8(&bytes).is_err();
+
+    let to_local = shared.upstream_to(|h| h.origin(&shared.local).to_string());
+    let to_local = as_pairs(&to_local);
+    let to_placeholder = shared.upstream_to(|h| h.placeholder().to_string());
+    let to_placeholder = as_pairs(&to_placeholder);
+
+    let mut response = Response::builder().status(status.as_u16());
+    let mut recorded_headers = Vec::new();
+    for (name, value) in &headers {
+        if SKIPPED_HEADERS.contains(name) {
+            continue;
+        }
+        let value = value.to_str().unwrap_or_default();
+        let is_cookie = name == header::SET_COOKIE;
+        let mut local = replace_origins(value, &to_local);
+        let mut recorded = shared.redact(&replace_origins(value, &to_placeholder));
+        if is_cookie {
+            local = localize_set_cookie(&local);
+            recorded = redact_set_cookie(&localize_set_cookie(&recorded));
+        }
+        response = response.header(name, local);
+        recorded_headers.push((name.to_string(), recorded));
+    }
+    let (local_body, recorded_body) = if binary {
+        (
+            bytes.clone(),
+            base64::engine::general_purpose::STANDARD.encode(&bytes),
+        )
+    } else {
+        let text = String::from_utf8_lossy(&bytes);
+        (
+            Bytes::from(replace_origins(&text, &to_local)),
+            shared.redact(&replace_origins(&text, &to_placeholder)),
+        )
+    };
+
+    // 请求中的本地地址先还原为上游地址，再替换为占位符
+    let recorded_request = |text: &str| {
+        shared.redact(&replace_origins(
+            &replace_origins(text, &to_upstream),
+            &to_placeholder,
+        ))
+    };
+    lock(&shared.exchanges).push(Exchange {
+        host,
+        method: method.to_string(),
+        uri: recorded_request(&uri),
+        request_body: recorded_request(&body),
+        status: status.as_u16(),
+        headers: recorded_headers,
+        body: recorded_body,
+        binary,
+    });
+    Ok(response
+        .body(axum::body::Body::from(local_body))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response()))
+}
+
+struct ReplayShared {
+    local: Endpoints,
+    /// 往返以及是否已被回放
+    exchanges: Mutex<Vec<(Exchange, bool)>>,
+}
+
+/// 服务器启动后才知道自己的地址，所以共享状态稍后填入
+type ReplayState = Arc<Mutex<Option<Arc<ReplayShared>>>>;
+
+/// 回放服务器：按请求的服务、方法和路径依次返回夹具中尚未使用的响应，不访问网络。
+/// 服务地址占位符会被替换为回放服务器的地址。
+pub struct Replayer {
+    shared: Arc<ReplayShared>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Replayer {
+    pub async fn start(fixture: Fixture) -> std::io::Result<Self> {
+        let state: ReplayState = Arc::new(Mutex::new(None));
+        let (bases, handles) = serve_hosts(|host| {
+            Router::new()
+                .fallback(replay)
+                .with_state((host, state.clone()))
+        })
+        .await?;
+        let shared = Arc::new(ReplayShared {
+            // 回放时不解密密码，任何有效的公钥都可以
+            local: local_endpoints(&bases, Endpoints::default().cas_public_key),
+            exchanges: Mutex::new(fixture.exchanges.into_iter().map(|e| (e, false)).collect()),
+        });
+        *lock(&state) = Some(shared.clone());
+        Ok(Self { shared, handles })
+    }
+
+    /// 指向回放服务器的地址配置
+    pub fn endpoints(&self) -> Endpoints {
+        self.shared.local.clone()
+    }
+
+    /// 尚未被回放的往返数量
+    pub fn remaining(&self) -> usize {
+        lock(&self.shared.exchanges)
+            .iter()
+            .filter(|(_, used)| !used)
+            .count()
+    }
+}
+
+impl Drop for Replayer {
+    fn drop(&mut self) {
+        self.handles.iter().for_each(JoinHandle::abort);
+    }
+}
+
+async fn replay(State((host, state)): State<(Host, ReplayState)>, req: Request) -> Response {
+    let Some(shared) = lock(&state).clone() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+    let method = req.method().to_string();
+    let path = req.uri().path();
+    let exchange = {
+        let mut exchanges = lock(&shared.exchanges);
+        exchanges
+            .iter_mut()
+            .find(|(e, used)| !used && e.host == host && e.method == method && e.path() == path)
+            .map(|(e, used)| {
+                *used = true;
+                e.clone()
+            })
+    };
+    let Some(exchange) = exchange else {
+        log::warn!("No recorded exchange for {method} {path} on {host:?}");
+        return (
+            StatusCode::NOT_FOUND,
+            format!("No recorded exchange for {method} {path} on {host:?}"),
+        )
+            .into_response();
+    };
+    let to_local: Vec<(&str, &str)> = HOSTS
+        .iter()
+        .map(|h| (h.placeholder(), h.origin(&shared.local)))
+        .collect();
+    let mut response = Response::builder().status(exchange.status);
+    for (name, value) in &exchange.headers {
+        let (Ok(name), Ok(value)) = (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(replace_origins(value, &to_local)),
+        ) else {
+            continue;
+        };
+        response = response.header(name, value);
+    }
+    let body = if exchange.binary {
+        base64::engine::general_purpose::STANDARD
+            .decode(&exchange.body)
+            .unwrap_or_default()
+    } else {
+        replace_origins(&exchange.body, &to_local).into_bytes()
+    };
+    response
+        .body(axum::body::Body::from(body))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
@@ -0,0 +1,255 @@
+use crate::course::{CourseInfo, TeachingClass};
+use crate::timetable::{Meeting, weekday_name};
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use std::path::Path;
+
+/// 节次与上课时间的对应关系
+#[derive(Debug, Clone)]
+pub struct PeriodTable {
+    /// 第 i 节课（从 1 开始）的开始和结束时间
+    periods: Vec<(NaiveTime, NaiveTime)>,
+}
+
+impl PeriodTable {
+    /// 按顺序给出每节课的开始和结束时间
+    pub fn new(periods: Vec<(NaiveTime, NaiveTime)>) -> Self {
+        Self { periods }
+    }
+
+    /// 兴庆校区的作息时间。夏季和冬季下午、晚上的时间不同，具体以学校公布的为准，
+    /// 不一致时用 [`Self::new`] 自定义。
+    pub fn xjtu(summer: bool) -> Self {
+        let morning = ["08:00", "09:00", "10:10", "11:10"];
+        let later = if summer {
+            [
+                "14:30", "15:30", "16:40", "17:40", "19:40", "20:40", "21:40",
+            ]
+        } else {
+            [
+                "14:00", "15:00", "16:10", "17:10", "19:10", "20:10", "21:10",
+            ]
+        };
+        let periods = morning
+            .iter()
+            .chain(later.iter())
+            .map(|start| {
+                let start = NaiveTime::parse_from_str(start, "%H:%M").expect("valid time");
+                (start, start + chrono::Duration::minutes(50))
+            })
+            .collect();
+        Self { periods }
+    }
+
+    /// 第 `period` 节课的时间
+    pub fn get(&self, period: u8) -> Option<(NaiveTime, NaiveTime)> {
+        self.periods
+            .get(usize::from(period).checked_sub(1)?)
+            .copied()
+    }
+}
+
+/// 课表日历，导出为 RFC 5545 的 iCalendar 文件。每次上课安排对应一个按周重复的事件。
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    /// 第一周的星期一
+    first_monday: NaiveDate,
+    periods: PeriodTable,
+    events: Vec<Event>,
+    skipped: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+struct Event {
+    uid: String,
+    summary: String,
+    location: Option<String>,
+    description: String,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    /// 按周重复的间隔和次数
+    interval: u32,
+    count: u32,
+    /// 重复范围内不上课的日期
+    exdates: Vec<NaiveDateTime>,
+}
+
+/// 日期时间使用北京时间
+const TZID: &str = "Asia/Shanghai";
+
+impl Calendar {
+    /// `semester_start` 为第一周中的任意一天
+    pub fn new(semester_start: NaiveDate, periods: PeriodTable) -> Self {
+        let first_monday =
+            semester_start - Days::new(u64::from(semester_start.weekday().num_days_from_monday()));
+        Self {
+            first_monday,
+            periods,
+            events: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// 第 `week` 周中 `meeting` 当天的日期
+    fn date(&self, week: u32, meeting: &Meeting) -> NaiveDate {
+        let days = (week - 1) * 7 + meeting.weekday.num_days_from_monday();
+        self.first_monday + Days::new(u64::from(days))
+    }
+
+    /// 加入一个教学班的所有上课安排
+    pub fn add_class(&mut self, course_name: &str, class: &TeachingClass) {
+        let schedule = class.schedule();
+        for text in schedule.unparsed {
+            log::warn!(
+                "Skipping unparsed schedule of {}: {text}",
+                class.teaching_class_id
+            );
+            self.skipped.push((class.teaching_class_id.clone(), text));
+        }
+        for (index, meeting) in schedule.meetings.iter().enumerate() {
+            let (Some((start, _)), Some((_, end))) = (
+                self.periods.get(meeting.first_period),
+                self.periods.get(meeting.last_period),
+            ) else {
+                self.skipped
+                    .push((class.teaching_class_id.clone(), meeting.to_string()));
+                continue;
+            };
+            let weeks: Vec<u32> = meeting.weeks.iter().collect();
+            let (first, last) = (weeks[0], weeks[weeks.len() - 1]);
+            // 等间隔的周次（包括单双周）直接用 INTERVAL，其余按每周重复并排除不上课的周
+            let step = if weeks.len() > 1 {
+                weeks[1] - weeks[0]
+            } else {
+                1
+            };
+            let regular = weeks.windows(2).all(|w| w[1] - w[0] == step);
+            let (interval, count, exdates) = if regular {
+                (step, weeks.len() as u32, Vec::new())
+            } else {
+                let exdates = (first..=last)
+                    .filter(|week| !meeting.weeks.contains(*week))
+                    .map(|week| self.date(week, meeting).and_time(start))
+                    .collect();
+                (1, last - first + 1, exdates)
+            };
+            let description = format!(
+                "教师：{}\n教学班：{}\n{} {}",
+                class.teacher_name,
+                class.teaching_class_id,
+                meeting.weeks,
+                weekday_name(meeting.weekday)
+            );
+            let date = self.date(first, meeting);
+            self.events.push(Event {
+                uid: format!("{}-{index}@xjtu-login", class.teaching_class_id),
+                summary: escape(course_name),
+                location: match (&meeting.campus, meeting.location()) {
+                    (Some(campus), Some(place)) => Some(escape(&format!("{campus} {place}"))),
+                    (campus, place) => campus.clone().or(place).map(|l| escape(&l)),
+                },
+                description: escape(&description),
+                start: date.and_time(start),
+                end: date.and_time(end),
+                interval,
+                count,
+                exdates,
+            });
+        }
+    }
+
+    /// 加入课程中当前用户已选的教学班
+    pub fn add_selected(&mut self, courses: &[CourseInfo]) {
+        for course in courses {
+            for class in course.tc_list.iter().filter(|c| c.is_choose) {
+                self.add_class(&course.course_name, class);
+            }
+        }
+    }
+
+    /// 无法解析或节次超出作息表而未导出的上课安排，参数为教学班 ID 和原文
+    pub fn skipped(&self) -> &[(String, String)] {
+        &self.skipped
+    }
+
+    /// iCalendar 文本，行以 CRLF 结尾
+    pub fn to_ics(&self) -> String {
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//xjtu-login//timetable//ZH".to_string(),
+            "CALSCALE:GREGORIAN".to_string(),
+            "X-WR-CALNAME:课表".to_string(),
+            format!("X-WR-TIMEZONE:{TZID}"),
+            "BEGIN:VTIMEZONE".to_string(),
+            format!("TZID:{TZID}"),
+            "BEGIN:STANDARD".to_string(),
+            "DTSTART:19700101T000000".to_string(),
+            "TZOFFSETFROM:+0800".to_string(),
+            "TZOFFSETTO:+0800".to_string(),
+            "TZNAME:CST".to_string(),
+            "END:STANDARD".to_string(),
+            "END:VTIMEZONE".to_string(),
+        ];
+        for event in &self.events {
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:{}", event.uid));
+            lines.push(format!("DTSTAMP:{stamp}"));
+            lines.push(format!("DTSTART;TZID={TZID}:{}", datetime(event.start)));
+            lines.push(format!("DTEND;TZID={TZID}:{}", datetime(event.end)));
+            lines.push(if event.interval == 1 {
+                format!("RRULE:FREQ=WEEKLY;COUNT={}", event.count)
+            } else {
+                format!(
+                    "RRULE:FREQ=WEEKLY;INTERVAL={};COUNT={}",
+                    event.interval, event.count
+                )
+            });
+            if !event.exdates.is_empty() {
+                let dates: Vec<String> = event.exdates.iter().map(|d| datetime(*d)).collect();
+                lines.push(format!("EXDATE;TZID={TZID}:{}", dates.join(",")));
+            }
+            lines.push(format!("SUMMARY:{}", event.summary));
+            if let Some(location) = &event.location {
+                lines.push(format!("LOCATION:{location}"));
+            }
+            lines.push(format!("DESCRIPTION:{}", event.description));
+            lines.push("END:VEVENT".to_string());
+        }
+        lines.push("END:VCALENDAR".to_string());
+        lines.iter().map(|line| fold(line)).collect()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_ics())
+    }
+}
+
+fn datetime(time: NaiveDateTime) -> String {
+    time.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/// 转义 TEXT 类型的值（RFC 5545 3.3.11）
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// 按 75 字节折行（RFC 5545 3.1），不拆开多字节字符，续行以空格开头
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
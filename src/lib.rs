@@ -1,2 +1,22 @@
+pub mod captcha;
+pub mod catalog;
 pub mod course;
+pub mod endpoints;
+#[cfg(feature = "test-support")]
+pub mod fixture;
+pub mod ical;
 pub mod login;
+pub mod managed;
+pub mod mfa;
+#[cfg(feature = "test-support")]
+pub mod mock;
+pub mod plan;
+pub mod render;
+pub mod schedule;
+pub mod service;
+pub mod sniper;
+pub mod solver;
+pub mod sso;
+pub mod store;
+pub mod timetable;
+pub mod webvpn;
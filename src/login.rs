@@ -1,32 +1,78 @@
+use crate::captcha::{self, CaptchaSolver};
+use crate::endpoints::Endpoints;
+use crate::mfa::{self, MfaDetect, MfaHandler};
+use crate::service::{self, BoxFuture, CasService, ServiceContext, ServiceUrl};
+use crate::webvpn::{self, AccessMode};
 use base64::Engine;
 use reqwest::StatusCode;
 use reqwest::cookie::Jar;
-use reqwest::{Client, Response};
+use reqwest::{Client, Response, Url};
+use reqwest_cookie_store::CookieStoreMutex;
 use rsa::pkcs8::DecodePublicKey;
 use rsa::rand_core::OsRng;
 use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 use std::fmt::Display;
 use std::sync::Arc;
 use thiserror::Error;
 
 pub static BROWSER_UA: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/139.0.0.0 Safari/537.36";
 
+/// 内置的服务。自定义服务请实现 [`CasService`]。
 #[derive(Debug)]
 pub enum Service {
     AiPlatform,
     CourseSelection,
 }
 
-impl Display for Service {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Service {
+    fn inner(&self) -> &dyn CasService {
         match self {
-            Service::AiPlatform => write!(f, "AI 平台"),
-            Service::CourseSelection => write!(f, "选课系统"),
+            Service::AiPlatform => &service::AiPlatform,
+            Service::CourseSelection => &service::CourseSelection,
         }
     }
 }
 
+impl CasService for Service {
+    fn name(&self) -> &str {
+        self.inner().name()
+    }
+
+    fn probe_url(&self, endpoints: &Endpoints) -> String {
+        self.inner().probe_url(endpoints)
+    }
+
+    fn login_url<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+    ) -> BoxFuture<'a, Result<String, LoginError>> {
+        self.inner().login_url(ctx)
+    }
+
+    fn is_landed(&self, resp: &Response) -> bool {
+        self.inner().is_landed(resp)
+    }
+
+    fn check_landing(&self, landing: &Response) -> Result<(), LoginError> {
+        self.inner().check_landing(landing)
+    }
+
+    fn after_login<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+        landing: &'a Response,
+    ) -> BoxFuture<'a, Result<(), LoginError>> {
+        self.inner().after_login(ctx, landing)
+    }
+}
+
+impl Display for Service {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Error)]
 pub enum LoginError {
@@ -38,20 +84,66 @@ pub enum LoginError {
     ExpectedRedirect(String, StatusCode),
     #[error("Login failed")]
     LoginFailed,
+    #[error("Bad username or password: {0}")]
+    BadCredentials(String),
+    #[error("Account locked: {0}")]
+    AccountLocked(String),
+    #[error("Password expired: {0}")]
+    PasswordExpired(String),
+    #[error("Captcha rejected: {0}")]
+    CaptchaRejected(String),
+    #[error("Too many login attempts: {0}")]
+    TooManyAttempts(String),
+    #[error("Login rejected: {0}")]
+    Rejected(String),
+    #[error("Login form field `{0}` not found on {1}")]
+    MissingFormField(&'static str, String),
+    #[error("Login page layout changed on {0}: {1}")]
+    PageLayoutChanged(String, String),
+    #[error("Password encryption failed: {0}")]
+    EncryptionFailed(String),
+    #[error("Unexpected status code {1} on {0}")]
+    UnexpectedStatus(String, StatusCode),
+    #[error("Captcha required but no solver was supplied or it gave up")]
+    CaptchaRequired,
+    #[error("CAS single sign-on session has expired")]
+    SsoExpired,
+    #[error("MFA required but no handler was supplied")]
+    MfaRequired,
+    #[error("MFA rejected: {0}")]
+    MfaRejected(String),
     #[error("Other error: {0}")]
     Other(String),
 }
 
+impl LoginError {
+    /// 重试是否可能成功。账号或密码本身有问题时继续重试只会导致账号被锁定。
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            LoginError::BadCredentials(_)
+                | LoginError::AccountLocked(_)
+                | LoginError::PasswordExpired(_)
+                | LoginError::TooManyAttempts(_)
+        )
+    }
+}
+
 /**
- * Truncate a string to a maximum length, appending "... (N truncated)" if it was truncated.
+ * Truncate a string to at most `max_len` bytes, appending "... (N truncated)" if it was truncated.
+ * The cut backs off to the nearest character boundary, so multi-byte text never panics.
  */
 pub fn truncate_string(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
         s.to_string()
     } else {
-        let mut truncated = s[..max_len].to_string();
+        let end = (0..=max_len)
+            .rev()
+            .find(|i| s.is_char_boundary(*i))
+            .unwrap_or(0);
+        let mut truncated = s[..end].to_string();
         truncated.push_str("... (");
-        truncated.push_str(&(s.len() - max_len).to_string());
+        truncated.push_str(&(s.len() - end).to_string());
         truncated.push_str(" truncated)");
         truncated
     }
@@ -59,7 +151,12 @@ pub fn truncate_string(s: &str, max_len: usize) -> String {
 
 pub struct LoginSuccess {
     pub client: Client,
-    pub cookie_jar: Arc<Jar>,
+    /// 可序列化的 cookie 存储，见 [`crate::store::SessionStore`]
+    pub cookie_jar: Arc<CookieStoreMutex>,
+    /// 是否经由 WebVPN 访问，之后对该服务的请求也需要改写 URL
+    pub webvpn: bool,
+    /// 登录时使用的服务地址，之后对服务的请求也使用这些地址
+    pub endpoints: Endpoints,
 }
 
 pub struct Session {
@@ -97,10 +194,28 @@ impl Session {
     }
 }
 
-async fn follow_redirects(
+/// 创建登录和后续请求共用的客户端，cookie 保存在 `cookie_jar` 中
+pub(crate) fn build_client(cookie_jar: Arc<CookieStoreMutex>) -> Result<Client, reqwest::Error> {
+    Client::builder()
+        .no_proxy() // 禁用 proxy，防止梯子故障。对于校外用户，我们转而使用webvpn登陆
+        .cookie_store(true)
+        .cookie_provider(cookie_jar)
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(BROWSER_UA)
+        .build()
+}
+
+/// 将 Location 头解析为绝对 URL。WebVPN 网关返回的 Location 通常是相对路径。
+fn resolve_location(base: &Url, location: &str) -> String {
+    base.join(location)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| location.to_string())
+}
+
+pub(crate) async fn follow_redirects(
     client: &Client,
     url: &str,
-    stop_condition: Option<&dyn Fn(&Response) -> bool>,
+    stop_condition: Option<&(dyn Fn(&Response) -> bool + Sync)>,
 ) -> Result<Response, LoginError> {
     let mut url = url.to_string();
     for _ in 0..10 {
@@ -121,7 +236,7 @@ async fn follow_redirects(
                     .to_str()
                     .map_err(|_| LoginError::ExpectedRedirect(url.clone(), resp.status()))?;
                 log::debug!("Redirect to: {location}");
-                url = location.to_string();
+                url = resolve_location(resp.url(), location);
             } else {
                 return Err(LoginError::ExpectedRedirect(url.clone(), resp.status()));
             }
@@ -132,215 +247,498 @@ async fn follow_redirects(
     Err(LoginError::Other("Too many redirects".to_string()))
 }
 
+/// 沿重定向链前进，直到下一个地址满足 `stop_at` 或遇到非重定向响应，返回该地址。
+/// 满足 `stop_at` 的地址本身不会被请求。
+pub(crate) async fn follow_until(
+    client: &Client,
+    url: &str,
+    stop_at: &(dyn Fn(&Url) -> bool + Sync),
+) -> Result<String, LoginError> {
+    let mut url = url.to_string();
+    for _ in 0..10 {
+        if Url::parse(&url).is_ok_and(|u| stop_at(&u)) {
+            return Ok(url);
+        }
+        let resp = client
+            .get(&url)
+            .send()
+            .await
+            .map_err(LoginError::RequestError)?;
+        let location = resp.headers().get("Location").and_then(|l| l.to_str().ok());
+        match location {
+            Some(location)
+                if resp.status() == StatusCode::MOVED_PERMANENTLY
+                    || resp.status() == StatusCode::FOUND =>
+            {
+                log::debug!("Redirect to: {location}");
+                url = resolve_location(resp.url(), location);
+            }
+            _ => return Ok(resp.url().to_string()),
+        }
+    }
+    Err(LoginError::Other("Too many redirects".to_string()))
+}
+
+pub(crate) fn expect_redirect(resp: &Response) -> Result<String, LoginError> {
+    if resp.status() != StatusCode::FOUND {
+        return Err(LoginError::ExpectedRedirect(
+            resp.url().as_str().to_string(),
+            resp.status(),
+        ));
+    }
+    let location = resp
+        .headers()
+        .get("Location")
+        .ok_or_else(|| {
+            LoginError::ExpectedRedirect(resp.url().as_str().to_string(), resp.status())
+        })?
+        .to_str()
+        .map_err(|_| {
+            LoginError::ExpectedRedirect(resp.url().as_str().to_string(), resp.status())
+        })?;
+    log::debug!("Redirect to: {location}");
+    Ok(resolve_location(resp.url(), location))
+}
+
+/// 登录选项
+#[derive(Default)]
+pub struct LoginOptions<'a> {
+    /// 访问方式
+    pub mode: AccessMode,
+    /// 账号需要二次验证时用于获取验证码，未提供时返回 [`LoginError::MfaRequired`]
+    pub mfa: Option<&'a dyn MfaHandler>,
+    /// 登录页要求验证码时用于识别，未提供时返回 [`LoginError::CaptchaRequired`]
+    pub captcha: Option<&'a dyn CaptchaSolver>,
+    /// 各服务的地址，默认为学校的正式服务
+    pub endpoints: Endpoints,
+}
+
 pub async fn login(
-    service: Service,
+    service: impl CasService,
     username: &str,
     password: &str,
 ) -> Result<LoginSuccess, LoginError> {
-    let cookie_jar = Arc::new(Jar::default());
-    let client = Client::builder()
-        .no_proxy() // 禁用 proxy，防止梯子故障。对于校外用户，我们转而使用webvpn登陆
-        .cookie_store(true)
-        .cookie_provider(cookie_jar.clone())
-        .redirect(reqwest::redirect::Policy::none())
-        .user_agent(BROWSER_UA)
-        .build()
-        .map_err(LoginError::RequestError)?;
-    log::info!("Logging in to service: {service}");
-    let login_url = match service {
-        Service::AiPlatform => {
-            let login_start: serde_json::Value = client
-                .post("https://ai.xjtu.edu.cn/api/auth/login")
-                .json(&serde_json::json!(  {"SSO":"Oauth","IdpID":"1","RedirectUrl":"/"}))
-                .send()
-                .await
-                .map_err(LoginError::RequestError)?
-                .json()
-                .await
-                .map_err(LoginError::RequestError)?;
-            if let serde_json::Value::Object(obj) = &login_start {
-                if let Some(serde_json::Value::String(url)) = obj.get("redirect_uri") {
-                    url.clone()
-                } else {
-                    return Err(LoginError::Other(format!(
-                        "No url found in login start response: {login_start}"
-                    )));
-                }
-            } else {
-                return Err(LoginError::Other(format!(
-                    "Unexpected login start response: {login_start}"
-                )));
-            }
-        }
-        Service::CourseSelection => follow_redirects(
-            &client,
-            "https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/*default/index.do",
-            None,
-        )
+    login_with_options(service, username, password, &LoginOptions::default()).await
+}
+
+/// 以指定的访问方式登录服务
+/// # Arguments
+/// * `mode` - 直接访问、经由 WebVPN 访问，或按可达性自动选择
+pub async fn login_with_mode(
+    service: impl CasService,
+    username: &str,
+    password: &str,
+    mode: AccessMode,
+) -> Result<LoginSuccess, LoginError> {
+    let options = LoginOptions {
+        mode,
+        ..Default::default()
+    };
+    login_with_options(service, username, password, &options).await
+}
+
+/// 按给定选项登录服务
+pub async fn login_with_options(
+    service: impl CasService,
+    username: &str,
+    password: &str,
+    options: &LoginOptions<'_>,
+) -> Result<LoginSuccess, LoginError> {
+    Ok(authenticate(service, username, password, options)
         .await?
-        .url()
-        .to_string(),
+        .login)
+}
+
+/// CAS 颁发的服务票据，以及携带票据访问服务后到达的页面
+pub struct ServiceTicket {
+    /// 服务票据，e.g. ST-123-abc
+    pub ticket: String,
+    /// CAS 重定向回服务时携带票据的地址
+    pub callback_url: String,
+    /// 携带票据请求 `callback_url` 得到的响应，不跟随其后的重定向，也不检查状态码
+    pub landing: Response,
+    /// 登录所用的客户端和 cookie
+    pub login: LoginSuccess,
+}
+
+/// 登录任意以 CAS 认证的服务，例如 ehall、jwxt、图书馆，返回 CAS 颁发的服务票据。
+/// 流程与 [`login_with_options`] 相同，包括 WebVPN、二次验证和验证码。
+/// # Arguments
+/// * `service_url` - 服务在 CAS 登录地址中的 `service=` 参数，不需要编码
+pub async fn service_ticket(
+    service_url: &str,
+    username: &str,
+    password: &str,
+    options: &LoginOptions<'_>,
+) -> Result<ServiceTicket, LoginError> {
+    authenticate(ServiceUrl::new(service_url), username, password, options)
+        .await?
+        .into_ticket()
+}
+
+/// 一次完整登录的中间结果
+pub(crate) struct Authenticated {
+    pub(crate) login: LoginSuccess,
+    /// CAS 重定向到的服务地址
+    pub(crate) callback_url: String,
+    pub(crate) landing: Response,
+}
+
+impl Authenticated {
+    pub(crate) fn into_ticket(self) -> Result<ServiceTicket, LoginError> {
+        let ticket = Url::parse(&self.callback_url)
+            .ok()
+            .and_then(|url| {
+                url.query_pairs()
+                    .find(|(k, _)| k == "ticket")
+                    .map(|(_, v)| v.into_owned())
+            })
+            .ok_or_else(|| {
+                LoginError::Other(format!("No ticket found in {}", self.callback_url))
+            })?;
+        Ok(ServiceTicket {
+            ticket,
+            callback_url: self.callback_url,
+            landing: self.landing,
+            login: self.login,
+        })
+    }
+}
+
+/// 按访问方式决定是否经由 WebVPN
+pub(crate) async fn use_webvpn(client: &Client, mode: AccessMode, probe_url: &str) -> bool {
+    match mode {
+        AccessMode::Direct => false,
+        AccessMode::WebVpn => true,
+        AccessMode::Auto => !webvpn::direct_reachable(client, probe_url).await,
+    }
+}
+
+/// 判断地址是否为 CAS 登录页（包括经 WebVPN 改写后的地址）
+pub(crate) fn is_cas_login_url(url: &Url) -> bool {
+    url.path().ends_with("/cas/login")
+}
+
+/// 携带票据回到服务，沿重定向链到达最终页面并执行服务的检查和登录后处理
+pub(crate) async fn land(
+    service: &impl CasService,
+    ctx: &ServiceContext<'_>,
+    callback_url: &str,
+) -> Result<Response, LoginError> {
+    let landing =
+        follow_redirects(ctx.client, callback_url, Some(&|r| service.is_landed(r))).await?;
+    service.check_landing(&landing)?;
+    service.after_login(ctx, &landing).await?;
+    Ok(landing)
+}
+
+async fn authenticate(
+    service: impl CasService,
+    username: &str,
+    password: &str,
+    options: &LoginOptions<'_>,
+) -> Result<Authenticated, LoginError> {
+    let cookie_jar = Arc::new(CookieStoreMutex::default());
+    let client = build_client(cookie_jar.clone()).map_err(LoginError::RequestError)?;
+    let endpoints = &options.endpoints;
+    let webvpn = use_webvpn(&client, options.mode, &service.probe_url(endpoints)).await;
+    if webvpn {
+        log::info!("Using WebVPN for {}", service.name());
+        login_webvpn(&client, username, password, options).await?;
+    }
+    log::info!("Logging in to service: {}", service.name());
+    let ctx = ServiceContext {
+        client: &client,
+        webvpn,
+        endpoints,
     };
+    let login_url = service.login_url(&ctx).await?;
 
-    let resp = follow_redirects(&client, &login_url, None).await?;
-    let post_endpoint = resp.url().to_string();
-    log::info!("Login POST endpoint: {post_endpoint}");
-    let html = resp.text().await.unwrap();
-    let document = Html::parse_document(&html);
+    let callback_url = cas_login(&client, &login_url, username, password, options, webvpn)
+        .await?
+        .ok_or_else(|| LoginError::ExpectedRedirect(login_url.clone(), StatusCode::OK))?;
+    let landing = land(&service, &ctx, &callback_url).await?;
+    Ok(Authenticated {
+        login: LoginSuccess {
+            client,
+            cookie_jar,
+            webvpn,
+            endpoints: endpoints.clone(),
+        },
+        callback_url,
+        landing,
+    })
+}
 
-    // 2. 创建一个 CSS 选择器来查找元素
-    let selector = Selector::parse(r#"input[name="execution"]"#).unwrap();
-    let execution = document
-        .select(&selector)
-        .next()
-        .unwrap()
-        .attr("value")
-        .unwrap();
-    let selector = Selector::parse(r#"input[name="submit"]"#).unwrap();
-    let submit = document
-        .select(&selector)
-        .next()
-        .unwrap()
-        .attr("value")
-        .unwrap();
-    let form = document.select(&selector).next().unwrap().parent().unwrap();
-    let inputs: Vec<_> = form
-        .children()
-        .filter_map(|c| {
-            c.value().as_element().and_then(|e| {
-                if e.name() == "input" {
-                    Some((e.attr("name").unwrap_or(""), e.attr("value").unwrap_or("")))
-                } else {
-                    None
-                }
+/// 登录 WebVPN 网关。网关本身也是 CAS 的客户端，登录成功后会下发网关的会话 cookie。
+pub(crate) async fn login_webvpn(
+    client: &Client,
+    username: &str,
+    password: &str,
+    options: &LoginOptions<'_>,
+) -> Result<(), LoginError> {
+    let webvpn_login_url = options.endpoints.webvpn_url(webvpn::WEBVPN_LOGIN_PATH);
+    let resp = follow_redirects(client, &webvpn_login_url, None).await?;
+    let login_url = resp.url().to_string();
+    let callback_url = cas_login(client, &login_url, username, password, options, false)
+        .await?
+        .ok_or_else(|| LoginError::ExpectedRedirect(login_url.clone(), StatusCode::OK))?;
+    let resp = follow_redirects(client, &callback_url, None).await?;
+    if resp.status() != StatusCode::OK {
+        return Err(LoginError::UnexpectedStatus(
+            resp.url().to_string(),
+            resp.status(),
+        ));
+    }
+    log::info!("WebVPN login succeeded");
+    Ok(())
+}
+
+/// CAS 登录页上需要回传的表单字段
+struct LoginForm {
+    execution: String,
+    fp_visitor_id: String,
+    /// 连续登录失败次数，失败过多时 CAS 会要求验证码
+    fail_n: String,
+    /// 需要填写验证码时的验证码图片地址
+    captcha_url: Option<String>,
+}
+
+impl LoginForm {
+    /// 解析登录页。`Html` 不是 `Send`，因此这里把字段都复制出来，避免跨越 await 持有文档。
+    fn parse(html: &str, page_url: &Url) -> Result<Self, LoginError> {
+        let missing = |field| LoginError::MissingFormField(field, page_url.to_string());
+        let document = Html::parse_document(html);
+
+        // 2. 创建一个 CSS 选择器来查找元素
+        let selector = Selector::parse(r#"input[name="execution"]"#).unwrap();
+        let execution = document
+            .select(&selector)
+            .next()
+            .and_then(|e| e.attr("value"))
+            .ok_or_else(|| missing("execution"))?;
+        let selector = Selector::parse(r#"input[name="submit"]"#).unwrap();
+        let submit = document
+            .select(&selector)
+            .next()
+            .ok_or_else(|| missing("submit"))?;
+        let form = submit.parent().ok_or_else(|| {
+            LoginError::PageLayoutChanged(
+                page_url.to_string(),
+                "submit button has no enclosing element".to_string(),
+            )
+        })?;
+        let inputs: Vec<_> = form
+            .children()
+            .filter_map(|c| {
+                c.value().as_element().and_then(|e| {
+                    if e.name() == "input" {
+                        Some((e.attr("name").unwrap_or(""), e.attr("value").unwrap_or("")))
+                    } else {
+                        None
+                    }
+                })
             })
+            .collect();
+        let fp_visitor_id = inputs
+            .iter()
+            .find(|p| p.0 == "fpVisitorId")
+            .ok_or_else(|| missing("fpVisitorId"))?
+            .1;
+        let fail_n = inputs
+            .iter()
+            .find(|p| p.0 == "failN")
+            .map(|p| p.1)
+            .filter(|v| !v.is_empty())
+            .unwrap_or("0");
+        // 登录页可能始终包含隐藏的验证码图片，由脚本在失败后显示；
+        // 只有图片可见或已经登录失败过时才需要验证码
+        let failed = fail_n.parse::<u32>().is_ok_and(|n| n > 0);
+        let selector = Selector::parse(r#"img[id*="captcha" i], img[src*="captcha" i]"#).unwrap();
+        let captcha_url = document
+            .select(&selector)
+            .find(|img| failed || !is_hidden(*img))
+            .and_then(|img| img.attr("src"))
+            .map(|src| resolve_location(page_url, src));
+        Ok(Self {
+            execution: execution.to_string(),
+            fp_visitor_id: fp_visitor_id.to_string(),
+            fail_n: fail_n.to_string(),
+            captcha_url,
         })
-        .collect();
-    let fp_visitor_id = inputs.iter().find(|p| p.0 == "fpVisitorId").unwrap().1;
-    log::info!(
-        "execution: {}, submit: {}, fpVisitorId: {}",
-        truncate_string(execution, 32),
-        submit,
-        fp_visitor_id
-    );
+    }
+}
+
+/// 元素或其祖先带有 `hidden` 属性，或样式中设置了 `display: none` / `visibility: hidden`
+fn is_hidden(element: ElementRef) -> bool {
+    std::iter::once(element)
+        .chain(element.ancestors().filter_map(ElementRef::wrap))
+        .any(|e| {
+            let style: String = e
+                .attr("style")
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_ascii_lowercase();
+            e.attr("hidden").is_some()
+                || style.contains("display:none")
+                || style.contains("visibility:hidden")
+        })
+}
 
-    // encrypt password
-    let public_key = RsaPublicKey::from_public_key_pem(include_str!("XJTU_PublicKey")).unwrap();
+/// 使用 CAS 的公钥加密密码，格式与登录页的 JS 一致
+fn encrypt_password(password: &str, public_key_pem: &str) -> Result<String, LoginError> {
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem)
+        .map_err(|e| LoginError::EncryptionFailed(format!("invalid public key: {e}")))?;
     let base64engine = base64::engine::general_purpose::STANDARD;
-    let password_encrypted = format!(
-        "__RSA__{}",
-        base64engine.encode(
-            public_key
-                .encrypt(&mut OsRng, Pkcs1v15Encrypt, password.as_bytes())
-                .expect("加密失败")
-        )
+    let encrypted = public_key
+        .encrypt(&mut OsRng, Pkcs1v15Encrypt, password.as_bytes())
+        .map_err(|e| LoginError::EncryptionFailed(e.to_string()))?;
+    Ok(format!("__RSA__{}", base64engine.encode(encrypted)))
+}
+
+/// 在 CAS 登录页上提交用户名和密码，返回 CAS 重定向回服务的地址；
+/// 登录页没有 `service=` 参数时 CAS 不重定向，返回 `None`
+/// # Arguments
+/// * `login_url` - CAS 登录页地址，可能已经过 WebVPN 改写
+/// * `webvpn` - 是否经由 WebVPN 访问 CAS 的其他接口
+pub(crate) async fn cas_login(
+    client: &Client,
+    login_url: &str,
+    username: &str,
+    password: &str,
+    options: &LoginOptions<'_>,
+    webvpn: bool,
+) -> Result<Option<String>, LoginError> {
+    let resp = follow_redirects(client, login_url, None).await?;
+    let page_url = resp.url().clone();
+    if resp.status() != StatusCode::OK {
+        return Err(LoginError::UnexpectedStatus(
+            page_url.to_string(),
+            resp.status(),
+        ));
+    }
+    log::info!("Login POST endpoint: {page_url}");
+    let html = resp.text().await.map_err(LoginError::RequestError)?;
+    let form = LoginForm::parse(&html, &page_url)?;
+    log::info!(
+        "execution: {}, fpVisitorId: {}, failN: {}",
+        truncate_string(&form.execution, 32),
+        form.fp_visitor_id,
+        form.fail_n
     );
+    let captcha = match &form.captcha_url {
+        Some(url) => {
+            log::info!("Captcha required");
+            let solver = options.captcha.ok_or(LoginError::CaptchaRequired)?;
+            let image = captcha::fetch(client, url).await?;
+            solver
+                .solve(&image)
+                .await
+                .ok_or(LoginError::CaptchaRequired)?
+                .trim()
+                .to_string()
+        }
+        None => String::new(),
+    };
+
+    let password_encrypted = encrypt_password(password, &options.endpoints.cas_public_key)?;
 
     // detect
     let resp = client
-        .post("https://login.xjtu.edu.cn/cas/mfa/detect")
+        .post(
+            options
+                .endpoints
+                .route(&options.endpoints.cas_url("/cas/mfa/detect"), webvpn),
+        )
         .form(&[
             ("username", username),
             ("password", &password_encrypted),
-            ("fpVisitorId", fp_visitor_id),
+            ("fpVisitorId", &form.fp_visitor_id),
         ])
         .send()
         .await
         .map_err(LoginError::RequestError)?;
     log::info!("Detecting MFA, status: {}", resp.status());
-    let mfa_state = if let Ok(json) = resp.json().await {
-        if let serde_json::Value::Object(obj) = json {
-            if let Some(serde_json::Value::Object(data)) = obj.get("data") {
-                if let Some(serde_json::Value::String(mfa_state)) = data.get("state") {
-                    mfa_state.clone()
-                } else {
-                    return Err(LoginError::MFADetectFailure(Some(
-                        serde_json::Value::Object(obj),
-                    )));
-                }
-            } else {
-                return Err(LoginError::MFADetectFailure(Some(
-                    serde_json::Value::Object(obj),
-                )));
-            }
-        } else {
-            return Err(LoginError::MFADetectFailure(Some(json)));
-        }
-    } else {
-        return Err(LoginError::MFADetectFailure(None));
-    };
+    let mfa = MfaDetect::parse(resp.json().await.ok())?;
+    if mfa.need {
+        log::info!("MFA required");
+        let handler = options.mfa.ok_or(LoginError::MfaRequired)?;
+        mfa::complete(client, &mfa.state, handler, &options.endpoints, webvpn).await?;
+    }
 
     let resp = client
-        .post(post_endpoint)
+        .post(page_url)
         .form(&[
             ("username", username),
             ("password", &password_encrypted),
-            ("execution", execution),
+            ("execution", &form.execution),
             ("submit1", "Login1"),
             ("_eventId", "submit"),
             ("geolocation", ""),
             ("trustAgent", ""),
-            ("fpVisitorId", fp_visitor_id),
+            ("fpVisitorId", &form.fp_visitor_id),
             ("trustAgent", ""),
-            ("captcha", ""),
+            ("captcha", &captcha),
             ("currentMenu", "1"),
-            ("failN", "0"),
-            ("mfaState", &mfa_state),
+            ("failN", &form.fail_n),
+            ("mfaState", &mfa.state),
         ])
         .send()
         .await
         .map_err(LoginError::RequestError)?;
-    fn expect_redirect(resp: &Response) -> Result<&str, LoginError> {
-        if resp.status() != StatusCode::FOUND {
-            return Err(LoginError::ExpectedRedirect(
-                resp.url().as_str().to_string(),
-                resp.status(),
-            ));
-        }
-        let location = resp
-            .headers()
-            .get("Location")
-            .ok_or_else(|| {
-                LoginError::ExpectedRedirect(resp.url().as_str().to_string(), resp.status())
-            })?
-            .to_str()
-            .map_err(|_| {
-                LoginError::ExpectedRedirect(resp.url().as_str().to_string(), resp.status())
-            })?;
-        log::debug!("Redirect to: {location}");
-        Ok(location)
-    }
-    match service {
-        Service::AiPlatform => {
-            let resp = follow_redirects(
-                &client,
-                expect_redirect(&resp)?,
-                Some(&|r| {
-                    r.status() != StatusCode::FOUND // not 302
-                || r.headers().get("Location") // success
-                        .and_then( |loc| loc.to_str()
-                        .ok().map(|s| s.starts_with("/login-success"))).unwrap_or(false)
-                }),
-            )
-            .await?;
-            if resp.status() != StatusCode::FOUND {
-                return Err(LoginError::ExpectedRedirect(
-                    resp.url().as_str().to_string(),
-                    resp.status(),
-                ));
-            }
-        }
-        Service::CourseSelection => {
-            println!("resp status: {}", resp.status());
-            let resp = follow_redirects(&client, expect_redirect(&resp)?, None).await?;
-            if resp.status() != StatusCode::OK {
-                panic!(
-                    "Unexpected status code: {} on {}",
-                    resp.status(),
-                    resp.url()
-                );
+    log::debug!("CAS form response status: {}", resp.status());
+    match resp.status() {
+        StatusCode::FOUND => expect_redirect(&resp).map(Some),
+        StatusCode::OK => {
+            // 登录失败时 CAS 以 200 重新显示登录页，错误原因写在页面上
+            let html = resp.text().await.map_err(LoginError::RequestError)?;
+            match rejection(&html) {
+                Some(e) => Err(e),
+                None => Ok(None),
             }
         }
+        status => Err(LoginError::UnexpectedStatus(resp.url().to_string(), status)),
+    }
+}
+
+/// 页面上可能显示 CAS 错误信息的元素
+const ERROR_MESSAGE_SELECTOR: &str = "#errorMsg, #errormsg, #showErrorTip, #msg, .errors, .error-msg, .alert-danger, .el-form-item__error";
+
+/// 如果页面是重新显示的登录表单，根据页面上的错误信息返回对应的错误
+fn rejection(html: &str) -> Option<LoginError> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse(r#"input[name="execution"]"#).unwrap();
+    document.select(&selector).next()?;
+    let selector = Selector::parse(ERROR_MESSAGE_SELECTOR).unwrap();
+    let message = document
+        .select(&selector)
+        .map(|e| e.text().collect::<String>().trim().to_string())
+        .find(|text| !text.is_empty());
+    log::info!("CAS rejected login: {message:?}");
+    Some(match message {
+        Some(message) => classify_rejection(message),
+        None => LoginError::LoginFailed,
+    })
+}
+
+/// 将 CAS 的错误信息映射为具体的错误。顺序有意义：
+/// "密码错误次数过多，账号已锁定" 这类信息应当归为账号锁定，
+/// "验证码已过期" 应当归为验证码错误而不是密码过期。
+fn classify_rejection(message: String) -> LoginError {
+    let has = |keywords: &[&str]| keywords.iter().any(|k| message.contains(k));
+    if has(&["锁定", "冻结", "禁用", "停用", "locked", "disabled"]) {
+        LoginError::AccountLocked(message)
+    } else if has(&["次数过多", "频繁", "稍后再试", "too many"]) {
+        LoginError::TooManyAttempts(message)
+    } else if has(&["验证码", "captcha"]) {
+        LoginError::CaptchaRejected(message)
+    } else if has(&["过期", "已失效", "修改密码", "expired"]) {
+        LoginError::PasswordExpired(message)
+    } else if has(&["密码", "用户名", "账号", "不存在", "credentials"]) {
+        LoginError::BadCredentials(message)
+    } else {
+        LoginError::Rejected(message)
     }
-    Ok(LoginSuccess { client, cookie_jar })
 }
@@ -1,26 +1,53 @@
-use crate::course::get_batch_list;
-use crate::login::Service;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::sleep;
-
-mod course;
-mod login;
+use xjtu_login::captcha::CaptchaImage;
+use xjtu_login::course::{self, VolunteerOutcome};
+use xjtu_login::managed::{Credentials, ManagedCourseSession};
+use xjtu_login::mfa::MfaChallenge;
+use xjtu_login::render::OutputFormat;
+use xjtu_login::sniper::{Sniper, SniperConfig, Target};
+use xjtu_login::store::SessionStore;
 
 #[tokio::main]
 async fn main() {
     let _ = dotenv::dotenv();
     log4rs::init_file("log4rs.yaml", Default::default()).unwrap();
-    let login = login::login(
-        Service::CourseSelection,
-        &std::env::var("USERNAME").unwrap(),
-        &std::env::var("PASSWORD").unwrap(),
-    )
-    .await
-    .expect("login failed");
-    let session = course::CourseSession::fron_client(login.client)
+    let renderer = std::env::var("OUTPUT_FORMAT")
+        .map(|format| format.parse::<OutputFormat>().unwrap())
+        .unwrap_or_default()
+        .renderer();
+    let prompt_mfa = |challenge: MfaChallenge| async move {
+        println!(
+            "验证码已通过{}发送至 {}，请输入：",
+            challenge.method, challenge.target
+        );
+        read_line().await
+    };
+    let prompt_captcha = |image: CaptchaImage| async move {
+        image.save("captcha.jpg").ok()?;
+        println!("需要验证码，图片已保存至 captcha.jpg，请输入：");
+        read_line().await
+    };
+    let mut credentials = Credentials::new(
+        std::env::var("USERNAME").unwrap(),
+        std::env::var("PASSWORD").unwrap(),
+    );
+    credentials.mfa = Some(Arc::new(prompt_mfa));
+    credentials.captcha = Some(Arc::new(prompt_captcha));
+    let store = SessionStore::new(
+        std::env::var("SESSION_FILE").unwrap_or_else(|_| "session.json".to_string()),
+    );
+    let (_login, session) = store
+        .course_session_or_login(
+            &credentials.username,
+            &credentials.password,
+            &credentials.options(),
+        )
         .await
-        .unwrap();
-    let batch = get_batch_list(&session.client)
+        .expect("login failed");
+    let session = ManagedCourseSession::from_session(session, credentials).with_store(store);
+    let batch = session
+        .get_batch_list()
         .await
         .unwrap()
         .into_iter()
@@ -28,28 +55,38 @@ async fn main() {
         .unwrap();
     let courses = session
         .list_course(&batch, course::CourseType::TJKC, 0, "国际结算")
-        .await;
-    session
-        .delete_volunteer(&batch, &courses[0].tc_list[0].teaching_class_id)
-        .await;
-    sleep(Duration::from_secs_f32(0.3)).await;
-    println!(
-        "{}",
-        session
-            .get_capacity(&courses[0].tc_list[0].teaching_class_id)
-            .await
-    );
-    session
-        .add_volunteer(
-            &batch,
-            &courses[0].tc_list[0].teaching_class_id,
-            course::CourseType::TJKC,
-        )
-        .await;
-    println!(
-        "{}",
-        session
-            .get_capacity(&courses[0].tc_list[0].teaching_class_id)
-            .await
-    );
+        .await
+        .unwrap();
+    print!("{}", renderer.courses(&courses));
+    let targets = courses[0]
+        .tc_list
+        .iter()
+        .map(|tc| Target::new(&tc.teaching_class_id, course::CourseType::TJKC))
+        .collect();
+    let report = Sniper::new(&session, &batch, targets)
+        .with_config(SniperConfig {
+            interval: Duration::from_millis(500),
+            ..Default::default()
+        })
+        .run()
+        .await
+        .unwrap();
+    for class_id in &report.obtained {
+        print!("{}", renderer.outcome(class_id, &VolunteerOutcome::Success));
+    }
+    for (class_id, outcome) in &report.abandoned {
+        print!("{}", renderer.outcome(class_id, outcome));
+    }
+}
+
+/// 在阻塞线程池中读取一行标准输入，不占用运行时的工作线程
+async fn read_line() -> Option<String> {
+    tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).ok()?;
+        Some(line.trim().to_string())
+    })
+    .await
+    .ok()
+    .flatten()
 }
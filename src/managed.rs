@@ -0,0 +1,264 @@
+use crate::captcha::CaptchaSolver;
+use crate::course::{
+    self, Batch, CapacityInfo, CourseError, CourseInfo, CoursePage, CourseSession, CourseType,
+    Gender, SwapOutcome, SwapSteps, VolunteerOutcome,
+};
+use crate::endpoints::Endpoints;
+use crate::login::{self, LoginOptions, LoginSuccess, Service};
+use crate::mfa::MfaHandler;
+use crate::store::SessionStore;
+use crate::webvpn::AccessMode;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+/// 登录凭据和选项，会话失效时用于重新登录
+#[derive(Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    /// 访问方式
+    pub mode: AccessMode,
+    /// 重新登录时如需二次验证则使用；无人值守运行时通常无法提供
+    pub mfa: Option<Arc<dyn MfaHandler>>,
+    /// 重新登录时如需验证码则使用
+    pub captcha: Option<Arc<dyn CaptchaSolver>>,
+    /// 各服务的地址
+    pub endpoints: Endpoints,
+}
+
+impl Credentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+            mode: AccessMode::default(),
+            mfa: None,
+            captcha: None,
+            endpoints: Endpoints::default(),
+        }
+    }
+
+    /// 对应的登录选项
+    pub fn options(&self) -> LoginOptions<'_> {
+        LoginOptions {
+            mode: self.mode,
+            mfa: self.mfa.as_deref(),
+            captcha: self.captcha.as_deref(),
+            endpoints: self.endpoints.clone(),
+        }
+    }
+}
+
+/// 自动续期的选课会话。请求遇到会话失效时重新登录、重新获取 token，并重试一次原请求。
+pub struct ManagedCourseSession {
+    credentials: Credentials,
+    session: RwLock<Arc<CourseSession>>,
+    /// 保证同一时间只有一个任务在重新登录
+    relogin: Mutex<()>,
+    /// 重新登录后将新会话保存到这里
+    store: Option<SessionStore>,
+}
+
+impl ManagedCourseSession {
+    /// 登录并创建会话
+    pub async fn login(credentials: Credentials) -> Result<Self, CourseError> {
+        let (_, session) = Self::fresh_session(&credentials).await?;
+        Ok(Self::from_session(session, credentials))
+    }
+
+    /// 包装已有的会话，例如从 [`SessionStore`] 恢复的会话
+    pub fn from_session(session: CourseSession, credentials: Credentials) -> Self {
+        Self {
+            credentials,
+            session: RwLock::new(Arc::new(session)),
+            relogin: Mutex::new(()),
+            store: None,
+        }
+    }
+
+    /// 重新登录后把新会话保存到 `store`
+    pub fn with_store(mut self, store: SessionStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// 当前的会话
+    pub async fn session(&self) -> Arc<CourseSession> {
+        self.session.read().await.clone()
+    }
+
+    async fn fresh_session(
+        credentials: &Credentials,
+    ) -> Result<(LoginSuccess, CourseSession), CourseError> {
+        let login = login::login_with_options(
+            Service::CourseSelection,
+            &credentials.username,
+            &credentials.password,
+            &credentials.options(),
+        )
+        .await
+        .map_err(CourseError::ReloginFailed)?;
+        let session = CourseSession::from_login(&login).await?;
+        Ok((login, session))
+    }
+
+    /// 替换已失效的会话。`expired` 是调用方发现失效的那个会话，
+    /// 如果在等待锁期间已被其他任务替换，则不再重复登录。
+    async fn relogin(&self, expired: &Arc<CourseSession>) -> Result<(), CourseError> {
+        let _guard = self.relogin.lock().await;
+        if !Arc::ptr_eq(&*self.session.read().await, expired) {
+            return Ok(());
+        }
+        log::info!("Course session expired, logging in again");
+        let (login, session) = Self::fresh_session(&self.credentials).await?;
+        if let Some(store) = &self.store
+            && let Err(e) = store.save(&login, Some(&session))
+        {
+            log::warn!("Failed to save session: {e}");
+        }
+        *self.session.write().await = Arc::new(session);
+        Ok(())
+    }
+
+    /// 以当前会话执行操作，会话失效时重新登录并重试一次
+    pub async fn run<T, F, Fut>(&self, op: F) -> Result<T, CourseError>
+    where
+        F: Fn(Arc<CourseSession>) -> Fut,
+        Fut: Future<Output = Result<T, CourseError>>,
+    {
+        let session = self.session().await;
+        match op(session.clone()).await {
+            Err(CourseError::SessionExpired) => {
+                self.relogin(&session).await?;
+                op(self.session().await).await
+            }
+            result => result,
+        }
+    }
+
+    /// 见 [`CourseSession::get_batch_list`]
+    pub async fn get_batch_list(&self) -> Result<Vec<Batch>, CourseError> {
+        self.run(|s| async move { s.get_batch_list().await }).await
+    }
+
+    /// 见 [`CourseSession::server_date`]
+    pub async fn server_date(&self) -> Result<DateTime<Utc>, CourseError> {
+        self.run(|s| async move { s.server_date().await }).await
+    }
+
+    /// 见 [`CourseSession::list_course`]
+    pub async fn list_course(
+        &self,
+        batch: &Batch,
+        course_type: CourseType,
+        page: u32,
+        query: &str,
+    ) -> Result<Vec<CourseInfo>, CourseError> {
+        self.run(|s| async move { s.list_course(batch, course_type, page, query).await })
+            .await
+    }
+
+    /// 见 [`CourseSession::list_course_page`]
+    pub async fn list_course_page(
+        &self,
+        batch: &Batch,
+        course_type: CourseType,
+        page: u32,
+        page_size: u32,
+        query: &str,
+    ) -> Result<CoursePage, CourseError> {
+        self.run(|s| async move {
+            s.list_course_page(batch, course_type, page, page_size, query)
+                .await
+        })
+        .await
+    }
+
+    /// 见 [`CourseSession::delete_volunteer`]
+    pub async fn delete_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+    ) -> Result<VolunteerOutcome, CourseError> {
+        self.run(|s| async move { s.delete_volunteer(batch, class_id).await })
+            .await
+    }
+
+    /// 见 [`CourseSession::add_volunteer`]
+    pub async fn add_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+        course_type: CourseType,
+    ) -> Result<VolunteerOutcome, CourseError> {
+        self.run(|s| async move { s.add_volunteer(batch, class_id, course_type).await })
+            .await
+    }
+
+    /// 见 [`CourseSession::swap_class`]。查询课容量、退选、选课和回滚各自在会话失效时重新登录后重试，
+    /// 而不是重新执行整个换班，所以退选之后 token 过期也不会丢掉原教学班。
+    pub async fn swap_class(
+        &self,
+        batch: &Batch,
+        from: &str,
+        to: &str,
+        course_type: CourseType,
+        gender: Option<Gender>,
+    ) -> Result<SwapOutcome, CourseError> {
+        course::swap(self, batch, from, to, course_type, gender).await
+    }
+
+    /// 见 [`CourseSession::get_capacity`]
+    pub async fn get_capacity(&self, class_id: &str) -> Result<CapacityInfo, CourseError> {
+        self.run(|s| async move { s.get_capacity(class_id).await })
+            .await
+    }
+
+    /// 启动后台保活任务：每隔 `interval` 检查一次会话，失效时立即重新登录。
+    /// 丢弃返回的句柄不会停止任务，需要停止时调用 `abort`。
+    pub fn spawn_keepalive(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let this = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let session = this.session().await;
+                match session.get_batch_list().await {
+                    Ok(_) => log::debug!("Course session keep-alive ok"),
+                    Err(CourseError::SessionExpired) => {
+                        if let Err(e) = this.relogin(&session).await {
+                            log::warn!("Keep-alive re-login failed: {e}");
+                        }
+                    }
+                    // 网络抖动等错误不代表会话失效，等下一轮再检查
+                    Err(e) => log::warn!("Keep-alive request failed: {e}"),
+                }
+            }
+        })
+    }
+}
+
+impl SwapSteps for ManagedCourseSession {
+    async fn get_capacity(&self, class_id: &str) -> Result<CapacityInfo, CourseError> {
+        ManagedCourseSession::get_capacity(self, class_id).await
+    }
+
+    async fn delete_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+    ) -> Result<VolunteerOutcome, CourseError> {
+        ManagedCourseSession::delete_volunteer(self, batch, class_id).await
+    }
+
+    async fn add_volunteer(
+        &self,
+        batch: &Batch,
+        class_id: &str,
+        course_type: CourseType,
+    ) -> Result<VolunteerOutcome, CourseError> {
+        ManagedCourseSession::add_volunteer(self, batch, class_id, course_type).await
+    }
+}
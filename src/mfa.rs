@@ -0,0 +1,188 @@
+use crate::endpoints::Endpoints;
+use crate::login::LoginError;
+use crate::service::BoxFuture;
+use reqwest::Client;
+use std::fmt::Display;
+use std::future::Future;
+
+/// 二次验证方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MfaMethod {
+    /// 短信验证码
+    #[default]
+    Sms,
+    /// 邮箱验证码
+    Email,
+}
+
+impl MfaMethod {
+    /// CAS 验证接口中的类型名
+    fn guard_name(&self) -> &'static str {
+        match self {
+            MfaMethod::Sms => "securephone",
+            MfaMethod::Email => "secureemail",
+        }
+    }
+
+    /// initByType 返回的脱敏接收方字段
+    fn target_field(&self) -> &'static str {
+        match self {
+            MfaMethod::Sms => "securePhone",
+            MfaMethod::Email => "secureEmail",
+        }
+    }
+}
+
+impl Display for MfaMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MfaMethod::Sms => write!(f, "短信"),
+            MfaMethod::Email => write!(f, "邮箱"),
+        }
+    }
+}
+
+/// 一次二次验证请求，验证码已经发出
+#[derive(Debug, Clone)]
+pub struct MfaChallenge {
+    /// 验证方式
+    pub method: MfaMethod,
+    /// 脱敏后的接收方，e.g. 138****0000
+    pub target: String,
+}
+
+/// 由调用方提供二次验证码，CLI、GUI 和测试可以各自实现。
+/// 等待输入是异步的，不会阻塞登录所在的运行时线程。
+pub trait MfaHandler: Send + Sync {
+    /// 选择验证方式，默认使用短信
+    fn method(&self) -> MfaMethod {
+        MfaMethod::Sms
+    }
+
+    /// 验证码发出后调用，返回用户输入的验证码；返回 `None` 表示放弃登录
+    fn provide_code<'a>(&'a self, challenge: &'a MfaChallenge) -> BoxFuture<'a, Option<String>>;
+}
+
+/// 接收验证请求、返回 future 的闭包，e.g. `|challenge| async move { ... }`
+impl<F, Fut> MfaHandler for F
+where
+    F: Fn(MfaChallenge) -> Fut + Send + Sync,
+    Fut: Future<Output = Option<String>> + Send + 'static,
+{
+    fn provide_code<'a>(&'a self, challenge: &'a MfaChallenge) -> BoxFuture<'a, Option<String>> {
+        Box::pin(self(challenge.clone()))
+    }
+}
+
+/// `/cas/mfa/detect` 的结果
+pub(crate) struct MfaDetect {
+    /// 是否需要二次验证
+    pub need: bool,
+    /// 提交登录表单时携带的 mfaState
+    pub state: String,
+}
+
+impl MfaDetect {
+    pub(crate) fn parse(json: Option<serde_json::Value>) -> Result<Self, LoginError> {
+        let Some(json) = json else {
+            return Err(LoginError::MFADetectFailure(None));
+        };
+        let data = &json["data"];
+        let Some(state) = data["state"].as_str() else {
+            return Err(LoginError::MFADetectFailure(Some(json)));
+        };
+        Ok(Self {
+            need: data["need"].as_bool().unwrap_or(false),
+            state: state.to_string(),
+        })
+    }
+}
+
+/// 从 CAS 的 JSON 响应中取出错误信息
+fn server_message(json: &serde_json::Value) -> String {
+    json["message"]
+        .as_str()
+        .or_else(|| json["msg"].as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| json.to_string())
+}
+
+async fn post_json(
+    client: &Client,
+    url: &str,
+    body: serde_json::Value,
+) -> Result<serde_json::Value, LoginError> {
+    client
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(LoginError::RequestError)?
+        .json()
+        .await
+        .map_err(LoginError::RequestError)
+}
+
+/// 完成二次验证：初始化验证方式、发送验证码、向调用方索取验证码并校验。
+/// 成功后 CAS 会将 mfaState 标记为已验证，随后即可提交登录表单。
+/// # Arguments
+/// * `state` - detect 返回的 mfaState
+/// * `webvpn` - 是否经由 WebVPN 访问 CAS
+pub(crate) async fn complete(
+    client: &Client,
+    state: &str,
+    handler: &dyn MfaHandler,
+    endpoints: &Endpoints,
+    webvpn: bool,
+) -> Result<(), LoginError> {
+    let method = handler.method();
+    let guard = method.guard_name();
+    let cas_url = |path: String| endpoints.route(&endpoints.cas_url(&path), webvpn);
+    let init: serde_json::Value = client
+        .get(cas_url(format!("/cas/mfa/initByType/{guard}")))
+        .query(&[("state", state)])
+        .send()
+        .await
+        .map_err(LoginError::RequestError)?
+        .json()
+        .await
+        .map_err(LoginError::RequestError)?;
+    let Some(gid) = init["data"]["gid"].as_str() else {
+        return Err(LoginError::MfaRejected(server_message(&init)));
+    };
+    let challenge = MfaChallenge {
+        method,
+        target: init["data"][method.target_field()]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    };
+
+    let sent = post_json(
+        client,
+        &cas_url(format!("/attest/api/guard/{guard}/send")),
+        serde_json::json!({ "gid": gid }),
+    )
+    .await?;
+    if sent["code"].as_i64() != Some(0) {
+        return Err(LoginError::MfaRejected(server_message(&sent)));
+    }
+    log::info!("MFA code sent via {} to {}", method, challenge.target);
+
+    let code = handler
+        .provide_code(&challenge)
+        .await
+        .ok_or_else(|| LoginError::MfaRejected("no code provided".to_string()))?;
+    let valid = post_json(
+        client,
+        &cas_url(format!("/attest/api/guard/{guard}/valid")),
+        serde_json::json!({ "gid": gid, "code": code.trim() }),
+    )
+    .await?;
+    // status 2 表示验证通过
+    if valid["code"].as_i64() != Some(0) || valid["data"]["status"].as_i64() != Some(2) {
+        return Err(LoginError::MfaRejected(server_message(&valid)));
+    }
+    log::info!("MFA verified");
+    Ok(())
+}
@@ -0,0 +1,1114 @@
+use crate::course::{CourseType, GenderLimit, parse_server_time};
+use crate::endpoints::Endpoints;
+use crate::webvpn::{self, WEBVPN_LOGIN_PATH};
+use axum::Router;
+use axum::extract::{Form, Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use base64::Engine;
+use chrono::{DateTime, TimeDelta, Utc};
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// 模拟 CAS 解密密码所用的测试私钥，对应的公钥由 [`MockServer::endpoints`] 提供给客户端
+const TEST_PRIVATE_KEY: &str = include_str!("mock_private_key.pem");
+
+/// 模拟的验证码图片内容，不是真正的图片
+const MOCK_CAPTCHA_IMAGE: &[u8] = b"mock captcha";
+
+/// 选课系统入口，未登录时重定向到 CAS
+const COURSE_INDEX_PATH: &str = "/xsxkapp/sys/xsxkapp/*default/index.do";
+
+/// 类似 ehall 的 CAS 客户端入口，见 [`MockServer::portal_service`]
+const PORTAL_LOGIN_PATH: &str = "/portal/login";
+
+type QueryParams = Query<HashMap<String, String>>;
+type FormParams = Form<HashMap<String, String>>;
+
+/// 模拟的选课批次
+#[derive(Debug, Clone)]
+pub struct MockBatch {
+    pub code: String,
+    pub name: String,
+    /// e.g. 2025-01-01 08:00:00
+    pub begin_time: String,
+    pub end_time: String,
+}
+
+impl MockBatch {
+    pub fn new(code: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            name: name.into(),
+            begin_time: "2025-01-01 08:00:00".to_string(),
+            end_time: "2025-12-31 22:00:00".to_string(),
+        }
+    }
+}
+
+/// 模拟的教学班。同一课程号的教学班在课程列表中归为一门课程。
+#[derive(Debug, Clone)]
+pub struct MockClass {
+    /// 教学班 ID
+    pub id: String,
+    pub course_number: String,
+    pub course_name: String,
+    pub course_type: CourseType,
+    pub teacher_name: String,
+    pub teaching_place: String,
+    pub limit_gender: GenderLimit,
+    /// 教学班容量
+    pub capacity: u32,
+    /// 已选人数，不含当前学生以外的变化时可以直接修改
+    pub selected: u32,
+    /// 当前学生是否已选
+    pub chosen: bool,
+    /// 是否与已选课程时间冲突，冲突时添加志愿会被拒绝
+    pub conflict: bool,
+    pub credit: u32,
+}
+
+impl MockClass {
+    /// 不限性别、无人选的主修推荐课程
+    pub fn new(
+        id: impl Into<String>,
+        course_number: impl Into<String>,
+        course_name: impl Into<String>,
+        capacity: u32,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            course_number: course_number.into(),
+            course_name: course_name.into(),
+            course_type: CourseType::TJKC,
+            teacher_name: "张老师".to_string(),
+            teaching_place: "1-16周 星期一 第1-2节 主楼A-101".to_string(),
+            limit_gender: GenderLimit::None,
+            capacity,
+            selected: 0,
+            chosen: false,
+            conflict: false,
+            credit: 2,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.selected >= self.capacity
+    }
+}
+
+/// 模拟服务器的行为，可以在测试过程中通过 [`MockServer::update`] 修改
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub username: String,
+    pub password: String,
+    /// 学生姓名
+    pub name: String,
+    /// 设置后登录需要二次验证，且只接受这个验证码
+    pub mfa_code: Option<String>,
+    pub batches: Vec<MockBatch>,
+    pub classes: Vec<MockClass>,
+    /// 是否处于选课时间内，否则添加和取消志愿都会被拒绝
+    pub selection_open: bool,
+    /// 已选教学班的学分上限
+    pub credit_limit: Option<u32>,
+    /// 服务器时钟相对真实时间的偏差，影响响应头 `Date` 和批次开始时间的判断
+    pub clock_skew: TimeDelta,
+    /// 设置后选课系统的接口返回这个 HTML 页面和 502，模拟维护或网关错误
+    pub maintenance: Option<String>,
+    /// 退选成功后立即使选课系统会话失效，模拟退选与选课之间 token 过期
+    pub expire_on_delete: bool,
+    /// 设置后登录页总是拒绝提交的密码并显示这条错误信息
+    pub login_error: Option<String>,
+    /// 课程列表每页最多返回的数量，请求更大的 `pageSize` 时按这个数量分页
+    pub max_page_size: Option<usize>,
+    /// 课程列表不返回 `totalCount`
+    pub omit_total_count: bool,
+    /// 设置后登录页始终包含隐藏的验证码图片，密码错误过一次之后显示图片并要求填写这个验证码
+    pub captcha: Option<String>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            username: "2200000000".to_string(),
+            password: "password".to_string(),
+            name: "测试学生".to_string(),
+            mfa_code: None,
+            batches: vec![MockBatch::new("B1", "2025-2026学年第一学期正选")],
+            classes: Vec::new(),
+            selection_open: true,
+            credit_limit: None,
+            clock_skew: TimeDelta::zero(),
+            maintenance: None,
+            expire_on_delete: false,
+            login_error: None,
+            max_page_size: None,
+            omit_total_count: false,
+            captcha: None,
+        }
+    }
+}
+
+/// 服务器颁发的各种凭据
+#[derive(Default)]
+struct Sessions {
+    next_id: u64,
+    /// 登录页下发的 execution
+    executions: HashSet<String>,
+    /// mfaState -> 是否已通过二次验证
+    mfa_states: HashMap<String, bool>,
+    /// 二次验证的 gid -> mfaState
+    mfa_gids: HashMap<String, String>,
+    /// CAS 会话（CASTGC cookie）
+    tgts: HashSet<String>,
+    /// 服务票据 -> service
+    tickets: HashMap<String, String>,
+    /// 选课系统会话（_WEU cookie）
+    course_sessions: HashSet<String>,
+    /// 选课系统 token
+    tokens: HashSet<String>,
+    /// 提交密码成功的次数
+    form_logins: usize,
+    /// 上次登录成功以来提交失败的次数，即登录页的 failN
+    failures: usize,
+    /// WebVPN 会话（wengine_vpn_ticket cookie）-> 网关代为保存的校内服务 cookie
+    vpn_sessions: HashMap<String, HashMap<String, String>>,
+    /// 经网关转发的请求数
+    gateway_requests: usize,
+}
+
+impl Sessions {
+    fn issue(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}-{}", self.next_id)
+    }
+}
+
+struct MockState {
+    scenario: Scenario,
+    sessions: Sessions,
+}
+
+struct Shared {
+    base_url: String,
+    /// 模拟 WebVPN 网关的地址
+    gateway_url: String,
+    /// 网关转发请求所用的客户端，不跟随重定向
+    gateway_client: reqwest::Client,
+    key: RsaPrivateKey,
+    state: Mutex<MockState>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 服务器时钟的当前时间
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now() + self.lock().scenario.clock_skew
+    }
+
+    /// 选课系统入口的完整地址，作为 CAS 的 service 参数
+    fn course_service(&self) -> String {
+        format!("{}{COURSE_INDEX_PATH}", self.base_url)
+    }
+
+    fn redirect_to_cas(&self) -> Response {
+        redirect(&format!(
+            "{}/cas/login?service={}",
+            self.base_url,
+            urlencoding::encode(&self.course_service())
+        ))
+    }
+
+    fn decrypt_password(&self, encrypted: &str) -> Option<String> {
+        let encrypted = encrypted.strip_prefix("__RSA__")?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encrypted)
+            .ok()?;
+        let plain = self.key.decrypt(Pkcs1v15Encrypt, &bytes).ok()?;
+        String::from_utf8(plain).ok()
+    }
+}
+
+/// 进程内的模拟服务器，在同一个地址上模拟 CAS（登录页、二次验证、票据重定向）和选课系统的接口，
+/// 另一个地址上模拟 WebVPN 网关。配合 [`MockServer::endpoints`] 即可离线运行完整的登录和选课流程。
+/// 服务器随本对象一起停止。
+pub struct MockServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    handles: [JoinHandle<()>; 2],
+}
+
+impl MockServer {
+    /// 在随机端口上启动服务器
+    pub async fn start(scenario: Scenario) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let gateway_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let key = RsaPrivateKey::from_pkcs8_pem(TEST_PRIVATE_KEY)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let gateway_client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .build()
+            .map_err(std::io::Error::other)?;
+        let shared = Arc::new(Shared {
+            base_url: format!("http://{addr}"),
+            gateway_url: format!("http://{}", gateway_listener.local_addr()?),
+            gateway_client,
+            key,
+            state: Mutex::new(MockState {
+                scenario,
+                sessions: Sessions::default(),
+            }),
+        });
+        // 选课系统入口的路径中有字面量 `*default`
+        let app = Router::new()
+            .without_v07_checks()
+            .route("/cas/login", get(login_page).post(login_submit))
+            .route("/cas/captcha.jpg", get(captcha_image))
+            .route("/cas/mfa/detect", post(mfa_detect))
+            .route("/cas/mfa/initByType/{guard}", get(mfa_init))
+            .route("/attest/api/guard/{guard}/send", post(mfa_send))
+            .route("/attest/api/guard/{guard}/valid", post(mfa_valid))
+            .route(COURSE_INDEX_PATH, get(course_index))
+            .route(PORTAL_LOGIN_PATH, get(portal_login))
+            .route("/xsxkapp/sys/xsxkapp/student/register.do", get(register))
+            .route("/xsxkapp/sys/xsxkapp/elective/batch.do", get(batch_list))
+            .route(
+                "/xsxkapp/sys/xsxkapp/elective/programCourse.do",
+                post(program_course),
+            )
+            .route(
+                "/xsxkapp/sys/xsxkapp/elective/volunteer.do",
+                post(volunteer),
+            )
+            .route(
+                "/xsxkapp/sys/xsxkapp/elective/deleteVolunteer.do",
+                get(delete_volunteer),
+            )
+            .route(
+                "/xsxkapp/sys/xsxkapp/elective/teachingclass/capacity.do",
+                get(capacity),
+            )
+            .layer(middleware::from_fn_with_state(shared.clone(), maintenance))
+            .layer(middleware::map_response_with_state(
+                shared.clone(),
+                date_header,
+            ))
+            .with_state(shared.clone());
+        let gateway = Router::new()
+            .route("/", get(gateway_index))
+            .route("/login", get(gateway_login))
+            .fallback(gateway_proxy)
+            .with_state(shared.clone());
+        let handle = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                log::warn!("Mock server stopped: {e}");
+            }
+        });
+        let gateway_handle = tokio::spawn(async move {
+            if let Err(e) = axum::serve(gateway_listener, gateway).await {
+                log::warn!("Mock gateway stopped: {e}");
+            }
+        });
+        Ok(Self {
+            addr,
+            shared,
+            handles: [handle, gateway_handle],
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// e.g. `http://127.0.0.1:12345`
+    pub fn base_url(&self) -> &str {
+        &self.shared.base_url
+    }
+
+    /// 类似 ehall 的 CAS 客户端的 `service=` 地址。有效票据换来重定向到一个返回 404 的页面，
+    /// 无效票据得到 403。
+    pub fn portal_service(&self) -> String {
+        format!("{}{PORTAL_LOGIN_PATH}", self.shared.base_url)
+    }
+
+    /// 指向本服务器的地址配置，公钥为测试密钥对的公钥，WebVPN 网关指向模拟网关
+    pub fn endpoints(&self) -> Endpoints {
+        let public_key = RsaPublicKey::from(&self.shared.key)
+            .to_public_key_pem(LineEnding::LF)
+            .expect("test key is valid");
+        Endpoints {
+            cas_public_key: public_key,
+            webvpn: self.shared.gateway_url.clone(),
+            ..Endpoints::with_base(self.base_url())
+        }
+    }
+
+    /// 修改场景，例如调整已选人数或开启二次验证
+    pub fn update<R>(&self, f: impl FnOnce(&mut Scenario) -> R) -> R {
+        f(&mut self.shared.lock().scenario)
+    }
+
+    /// 当前场景的副本
+    pub fn scenario(&self) -> Scenario {
+        self.shared.lock().scenario.clone()
+    }
+
+    /// 使所有选课系统会话和 token 失效，CAS 会话保留
+    pub fn expire_course_sessions(&self) {
+        let mut state = self.shared.lock();
+        state.sessions.course_sessions.clear();
+        state.sessions.tokens.clear();
+    }
+
+    /// 使所有 CAS 会话失效，之后访问服务需要重新提交密码
+    pub fn expire_cas_sessions(&self) {
+        self.shared.lock().sessions.tgts.clear();
+    }
+
+    /// 提交密码并登录成功的次数
+    pub fn form_logins(&self) -> usize {
+        self.shared.lock().sessions.form_logins
+    }
+
+    /// 经模拟网关转发的请求数
+    pub fn gateway_requests(&self) -> usize {
+        self.shared.lock().sessions.gateway_requests
+    }
+
+    /// 已颁发但还没有被服务验证的票据数
+    pub fn unused_tickets(&self) -> usize {
+        self.shared.lock().sessions.tickets.len()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handles.iter().for_each(JoinHandle::abort);
+    }
+}
+
+fn redirect(location: &str) -> Response {
+    (
+        StatusCode::FOUND,
+        [(header::LOCATION, location.to_string())],
+    )
+        .into_response()
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+fn with_ticket(service: &str, ticket: &str) -> String {
+    let separator = if service.contains('?') { '&' } else { '?' };
+    format!("{service}{separator}ticket={ticket}")
+}
+
+/// 渲染登录页，`error` 为登录失败时显示的错误信息
+fn login_form(state: &mut MockState, error: Option<&str>) -> Response {
+    let execution = state.sessions.issue("e1s1");
+    state.sessions.executions.insert(execution.clone());
+    let error = error
+        .map(|e| format!(r#"<div id="errorMsg">{e}</div>"#))
+        .unwrap_or_default();
+    let failures = state.sessions.failures;
+    let captcha = match &state.scenario.captcha {
+        Some(_) if failures > 0 => {
+            r#"<input type="text" name="captcha"/><img id="captchaImg" src="/cas/captcha.jpg"/>"#
+        }
+        Some(_) => {
+            r#"<div style="display: none"><input type="text" name="captcha"/><img id="captchaImg" src="/cas/captcha.jpg"/></div>"#
+        }
+        None => "",
+    };
+    Html(format!(
+        r#"<html><body>
+<form id="fm1" method="post">
+<input type="text" name="username"/>
+<input type="password" name="password"/>
+<input type="hidden" name="execution" value="{execution}"/>
+<input type="hidden" name="fpVisitorId" value="mock-visitor"/>
+<input type="hidden" name="failN" value="{failures}"/>
+{captcha}
+<input type="submit" name="submit" value="登录"/>
+</form>
+{error}
+</body></html>"#
+    ))
+    .into_response()
+}
+
+fn login_success_page() -> Response {
+    Html("<html><body><h2>登录成功</h2></body></html>").into_response()
+}
+
+async fn login_page(
+    State(shared): State<Arc<Shared>>,
+    Query(query): QueryParams,
+    headers: HeaderMap,
+) -> Response {
+    let mut state = shared.lock();
+    let has_tgt = cookie(&headers, "CASTGC").is_some_and(|t| state.sessions.tgts.contains(t));
+    if !has_tgt {
+        return login_form(&mut state, None);
+    }
+    match query.get("service") {
+        Some(service) => {
+            let ticket = state.sessions.issue("ST");
+            state
+                .sessions
+                .tickets
+                .insert(ticket.clone(), service.clone());
+            redirect(&with_ticket(service, &ticket))
+        }
+        None => login_success_page(),
+    }
+}
+
+async fn login_submit(
+    State(shared): State<Arc<Shared>>,
+    Query(query): QueryParams,
+    Form(form): FormParams,
+) -> Response {
+    let field = |name: &str| form.get(name).map(String::as_str).unwrap_or_default();
+    let password = shared.decrypt_password(field("password"));
+    let mut state = shared.lock();
+    if !state.sessions.executions.remove(field("execution")) {
+        return login_form(&mut state, Some("页面已过期，请刷新后重试"));
+    }
+    if let Some(error) = state.scenario.login_error.clone() {
+        return login_form(&mut state, Some(&error));
+    }
+    if state.sessions.failures > 0
+        && let Some(answer) = &state.scenario.captcha
+        && field("captcha") != answer
+    {
+        state.sessions.failures += 1;
+        return login_form(&mut state, Some("验证码错误"));
+    }
+    if field("username") != state.scenario.username
+        || password.as_deref() != Some(state.scenario.password.as_str())
+    {
+        state.sessions.failures += 1;
+        return login_form(&mut state, Some("用户名或密码错误"));
+    }
+    if state.scenario.mfa_code.is_some()
+        && state.sessions.mfa_states.get(field("mfaState")) != Some(&true)
+    {
+        return login_form(&mut state, Some("请先完成二次认证"));
+    }
+    state.sessions.form_logins += 1;
+    state.sessions.failures = 0;
+    let tgt = state.sessions.issue("TGT");
+    state.sessions.tgts.insert(tgt.clone());
+    let set_cookie = format!("CASTGC={tgt}; Path=/; HttpOnly");
+    let response = match query.get("service") {
+        Some(service) => {
+            let ticket = state.sessions.issue("ST");
+            state
+                .sessions
+                .tickets
+                .insert(ticket.clone(), service.clone());
+            redirect(&with_ticket(service, &ticket))
+        }
+        None => login_success_page(),
+    };
+    ([(header::SET_COOKIE, set_cookie)], response).into_response()
+}
+
+async fn captcha_image() -> Response {
+    ([(header::CONTENT_TYPE, "image/jpeg")], MOCK_CAPTCHA_IMAGE).into_response()
+}
+
+async fn mfa_detect(State(shared): State<Arc<Shared>>) -> Response {
+    let mut state = shared.lock();
+    let mfa_state = state.sessions.issue("mfa");
+    state.sessions.mfa_states.insert(mfa_state.clone(), false);
+    let need = state.scenario.mfa_code.is_some();
+    axum::Json(json!({"code": 0, "data": {"need": need, "state": mfa_state}})).into_response()
+}
+
+async fn mfa_init(
+    State(shared): State<Arc<Shared>>,
+    Path(_guard): Path<String>,
+    Query(query): QueryParams,
+) -> Response {
+    let mut state = shared.lock();
+    let Some(mfa_state) = query
+        .get("state")
+        .filter(|s| state.sessions.mfa_states.contains_key(*s))
+        .cloned()
+    else {
+        return axum::Json(json!({"code": 1, "message": "无效的 state"})).into_response();
+    };
+    let gid = state.sessions.issue("gid");
+    state.sessions.mfa_gids.insert(gid.clone(), mfa_state);
+    axum::Json(json!({
+        "code": 0,
+        "data": {
+            "gid": gid,
+            "securePhone": "138****0000",
+            "secureEmail": "t***@xjtu.edu.cn"
+        }
+    }))
+    .into_response()
+}
+
+async fn mfa_send(
+    State(shared): State<Arc<Shared>>,
+    Path(_guard): Path<String>,
+    axum::Json(body): axum::Json<Value>,
+) -> Response {
+    let state = shared.lock();
+    let known = body["gid"]
+        .as_str()
+        .is_some_and(|gid| state.sessions.mfa_gids.contains_key(gid));
+    if known {
+        axum::Json(json!({"code": 0})).into_response()
+    } else {
+        axum::Json(json!({"code": 1, "message": "无效的 gid"})).into_response()
+    }
+}
+
+async fn mfa_valid(
+    State(shared): State<Arc<Shared>>,
+    Path(_guard): Path<String>,
+    axum::Json(body): axum::Json<Value>,
+) -> Response {
+    let mut state = shared.lock();
+    let mfa_state = body["gid"]
+        .as_str()
+        .and_then(|gid| state.sessions.mfa_gids.get(gid))
+        .cloned();
+    let Some(mfa_state) = mfa_state else {
+        return axum::Json(json!({"code": 1, "message": "无效的 gid"})).into_response();
+    };
+    if body["code"].as_str() != state.scenario.mfa_code.as_deref() {
+        return axum::Json(json!({"code": 0, "data": {"status": 3}, "message": "验证码错误"}))
+            .into_response();
+    }
+    state.sessions.mfa_states.insert(mfa_state, true);
+    axum::Json(json!({"code": 0, "data": {"status": 2}})).into_response()
+}
+
+/// 选课系统入口：携带票据时建立会话，已有会话时显示首页，否则重定向到 CAS
+async fn course_index(
+    State(shared): State<Arc<Shared>>,
+    Query(query): QueryParams,
+    headers: HeaderMap,
+) -> Response {
+    let mut state = shared.lock();
+    if let Some(ticket) = query.get("ticket") {
+        let service = state.sessions.tickets.remove(ticket);
+        if service.as_deref() != Some(shared.course_service().as_str()) {
+            return shared.redirect_to_cas();
+        }
+        let session = state.sessions.issue("WEU");
+        state.sessions.course_sessions.insert(session.clone());
+        let set_cookie = format!("_WEU={session}; Path=/");
+        return (
+            [(header::SET_COOKIE, set_cookie)],
+            redirect(&shared.course_service()),
+        )
+            .into_response();
+    }
+    if cookie(&headers, "_WEU").is_some_and(|s| state.sessions.course_sessions.contains(s)) {
+        Html("<html><body>选课系统</body></html>").into_response()
+    } else {
+        shared.redirect_to_cas()
+    }
+}
+
+async fn portal_login(State(shared): State<Arc<Shared>>, Query(query): QueryParams) -> Response {
+    let mut state = shared.lock();
+    let service = query
+        .get("ticket")
+        .and_then(|ticket| state.sessions.tickets.remove(ticket));
+    if service.as_deref() == Some(format!("{}{PORTAL_LOGIN_PATH}", shared.base_url).as_str()) {
+        redirect("/portal/index")
+    } else {
+        StatusCode::FORBIDDEN.into_response()
+    }
+}
+
+async fn register(State(shared): State<Arc<Shared>>, headers: HeaderMap) -> Response {
+    let mut state = shared.lock();
+    if !cookie(&headers, "_WEU").is_some_and(|s| state.sessions.course_sessions.contains(s)) {
+        return shared.redirect_to_cas();
+    }
+    let token = state.sessions.issue("token");
+    state.sessions.tokens.insert(token.clone());
+    axum::Json(json!({
+        "code": "1",
+        "msg": "成功",
+        "data": {
+            "number": state.scenario.username,
+            "name": state.scenario.name,
+            "token": token
+        }
+    }))
+    .into_response()
+}
+
+/// 检查选课接口的会话和 token
+fn authorized(state: &MockState, headers: &HeaderMap) -> bool {
+    let has_session =
+        cookie(headers, "_WEU").is_some_and(|s| state.sessions.course_sessions.contains(s));
+    let has_token = headers
+        .get("token")
+        .and_then(|t| t.to_str().ok())
+        .is_some_and(|t| state.sessions.tokens.contains(t));
+    has_session && has_token
+}
+
+/// 会话或 token 失效时选课系统返回的未登录业务码
+fn session_expired() -> Response {
+    axum::Json(json!({"code": "302", "msg": "登录已过期，请重新登录"})).into_response()
+}
+
+/// 从表单或查询参数中取出 JSON 形式的参数
+fn json_param(params: &HashMap<String, String>, name: &str) -> Value {
+    params
+        .get(name)
+        .and_then(|v| serde_json::from_str(v).ok())
+        .unwrap_or(Value::Null)
+}
+
+fn failure(msg: &str) -> Response {
+    axum::Json(json!({"code": "0", "msg": msg})).into_response()
+}
+
+fn flag(value: bool) -> &'static str {
+    if value { "1" } else { "0" }
+}
+
+async fn batch_list(State(shared): State<Arc<Shared>>, headers: HeaderMap) -> Response {
+    let state = shared.lock();
+    if !authorized(&state, &headers) {
+        return session_expired();
+    }
+    let batches: Vec<Value> = state
+        .scenario
+        .batches
+        .iter()
+        .map(|b| {
+            json!({
+                "batchType": "1",
+                "beginTime": b.begin_time,
+                "code": b.code,
+                "endTime": b.end_time,
+                "name": b.name,
+                "schoolTerm": "2025-2026-1",
+                "schoolTermName": "2025-2026学年第一学期",
+                "tacticCode": "1",
+                "tacticName": "可选可退",
+                "typeCode": "01",
+                "typeName": "正选",
+                "weekRange": "1-16周"
+            })
+        })
+        .collect();
+    axum::Json(json!({"code": "1", "msg": "成功", "dataList": batches})).into_response()
+}
+
+async fn program_course(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    Form(form): FormParams,
+) -> Response {
+    let state = shared.lock();
+    if !authorized(&state, &headers) {
+        return session_expired();
+    }
+    let setting = json_param(&form, "querySetting");
+    let data = &setting["data"];
+    let course_type = data["teachingClassType"].as_str().unwrap_or_default();
+    let query = data["queryContent"].as_str().unwrap_or_default();
+    let page_size: usize = setting["pageSize"]
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10)
+        .min(state.scenario.max_page_size.unwrap_or(usize::MAX));
+    let page: usize = setting["pageNumber"]
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+
+    // 按课程号归并教学班，保持场景中的顺序
+    let mut courses: Vec<(&str, Vec<&MockClass>)> = Vec::new();
+    for class in &state.scenario.classes {
+        if format!("{:?}", class.course_type) != course_type
+            || !(class.course_name.contains(query) || class.course_number.contains(query))
+        {
+            continue;
+        }
+        match courses.iter_mut().find(|(n, _)| *n == class.course_number) {
+            Some((_, classes)) => classes.push(class),
+            None => courses.push((&class.course_number, vec![class])),
+        }
+    }
+    let total = courses.len();
+    let data_list: Vec<Value> = courses
+        .iter()
+        .skip(page * page_size)
+        .take(page_size)
+        .map(|(number, classes)| {
+            let tc_list: Vec<Value> = classes
+                .iter()
+                .map(|c| {
+                    json!({
+                        "courseNumber": c.course_number,
+                        "teachingClassID": c.id,
+                        "teacherName": c.teacher_name,
+                        "teachingPlace": c.teaching_place,
+                        "classCapacity": c.capacity.to_string(),
+                        "numberOfSelected": c.selected.to_string(),
+                        "limitGender": c.limit_gender,
+                        "isChoose": flag(c.chosen),
+                        "isFull": flag(c.is_full()),
+                        "isConflict": flag(c.conflict)
+                    })
+                })
+                .collect();
+            json!({
+                "departmentName": "测试学院",
+                "courseNatureName": "必修",
+                "selected": classes.iter().any(|c| c.chosen),
+                "tcList": tc_list,
+                "courseNumber": number,
+                "courseName": classes[0].course_name,
+                "type": "01",
+                "typeName": "专业必修课程",
+                "hours": "32",
+                "credit": classes[0].credit.to_string(),
+                "majorFlag": "主修"
+            })
+        })
+        .collect();
+    let mut body = json!({"code": "1", "msg": "成功", "totalCount": total, "dataList": data_list});
+    if state.scenario.omit_total_count {
+        body.as_object_mut()
+            .expect("response is an object")
+            .remove("totalCount");
+    }
+    axum::Json(body).into_response()
+}
+
+/// 维护期间选课系统的接口返回 HTML 错误页面，CAS 不受影响
+async fn maintenance(State(shared): State<Arc<Shared>>, request: Request, next: Next) -> Response {
+    let page = shared.lock().scenario.maintenance.clone();
+    match page {
+        Some(page)
+            if request
+                .uri()
+                .path()
+                .starts_with("/xsxkapp/sys/xsxkapp/elective/") =>
+        {
+            (StatusCode::BAD_GATEWAY, Html(page)).into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
+
+/// 按服务器时钟设置响应头 `Date`，hyper 只在没有这个头时才自动添加
+async fn date_header(State(shared): State<Arc<Shared>>, mut response: Response) -> Response {
+    let date = shared.now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    if let Ok(value) = date.parse() {
+        response.headers_mut().insert(header::DATE, value);
+    }
+    response
+}
+
+async fn volunteer(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    Form(form): FormParams,
+) -> Response {
+    let mut state = shared.lock();
+    if !authorized(&state, &headers) {
+        return session_expired();
+    }
+    if !state.scenario.selection_open {
+        return failure("当前不在选课时间范围内");
+    }
+    let param = json_param(&form, "addParam");
+    let class_id = param["data"]["teachingClassId"]
+        .as_str()
+        .unwrap_or_default();
+    let now = Utc::now() + state.scenario.clock_skew;
+    let batch_code = param["data"]["electiveBatchCode"].as_str();
+    let not_started = state.scenario.batches.iter().any(|b| {
+        Some(b.code.as_str()) == batch_code
+            && parse_server_time(&b.begin_time).is_ok_and(|begin| now < begin)
+    });
+    if not_started {
+        return failure("当前不在选课时间范围内");
+    }
+    let credit_limit = state.scenario.credit_limit;
+    let classes = &mut state.scenario.classes;
+    let Some(index) = classes.iter().position(|c| c.id == class_id) else {
+        return failure("教学班不存在");
+    };
+    let course_number = classes[index].course_number.clone();
+    if classes
+        .iter()
+        .any(|c| c.chosen && c.course_number == course_number)
+    {
+        return failure("该课程已选，不能重复选择");
+    }
+    let class = &mut classes[index];
+    if class.conflict {
+        return failure("与已选课程时间冲突");
+    }
+    if class.is_full() {
+        return failure("课容量已满");
+    }
+    let credit = class.credit;
+    let chosen_credits: u32 = classes.iter().filter(|c| c.chosen).map(|c| c.credit).sum();
+    if credit_limit.is_some_and(|limit| chosen_credits + credit > limit) {
+        return failure("超出本学期学分上限");
+    }
+    let class = &mut classes[index];
+    class.selected += 1;
+    class.chosen = true;
+    axum::Json(json!({"code": "1", "msg": "添加选课志愿成功"})).into_response()
+}
+
+async fn delete_volunteer(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    Query(query): QueryParams,
+) -> Response {
+    let mut state = shared.lock();
+    if !authorized(&state, &headers) {
+        return session_expired();
+    }
+    if !state.scenario.selection_open {
+        return failure("当前不在选课时间范围内");
+    }
+    let param = json_param(&query, "deleteParam");
+    let class_id = param["data"]["teachingClassId"]
+        .as_str()
+        .unwrap_or_default();
+    let Some(class) = state
+        .scenario
+        .classes
+        .iter_mut()
+        .find(|c| c.id == class_id && c.chosen)
+    else {
+        return failure("未选择该教学班");
+    };
+    class.selected = class.selected.saturating_sub(1);
+    class.chosen = false;
+    if state.scenario.expire_on_delete {
+        state.sessions.course_sessions.clear();
+        state.sessions.tokens.clear();
+    }
+    axum::Json(json!({"code": "1", "msg": "退选成功"})).into_response()
+}
+
+async fn capacity(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    Query(query): QueryParams,
+) -> Response {
+    let state = shared.lock();
+    if !authorized(&state, &headers) {
+        return session_expired();
+    }
+    let class_id = query.get("teachingClassId").map(String::as_str);
+    let Some(class) = state
+        .scenario
+        .classes
+        .iter()
+        .find(|c| Some(c.id.as_str()) == class_id)
+    else {
+        return failure("教学班不存在");
+    };
+    // 限制性别的教学班，容量和人数都计入对应性别
+    let by_gender = |gender: GenderLimit| {
+        if class.limit_gender == gender {
+            (class.selected, class.capacity)
+        } else {
+            (0, 0)
+        }
+    };
+    let (number_of_male, capacity_of_male) = by_gender(GenderLimit::Male);
+    let (number_of_female, capacity_of_female) = by_gender(GenderLimit::Female);
+    axum::Json(json!({
+        "code": "1",
+        "msg": "成功",
+        "data": {
+            "numberOfMale": number_of_male.to_string(),
+            "capacityOfMale": capacity_of_male.to_string(),
+            "numberOfFemale": number_of_female.to_string(),
+            "capacityOfFemale": capacity_of_female.to_string(),
+            "numberOfSelected": class.selected.to_string(),
+            "classCapacity": class.capacity.to_string()
+        }
+    }))
+    .into_response()
+}
+
+/// 网关本身是 CAS 的客户端，登录后以 wengine_vpn_ticket cookie 识别会话
+const VPN_COOKIE: &str = "wengine_vpn_ticket";
+
+/// 转发时不原样传递的请求头和响应头
+const HOP_HEADERS: [header::HeaderName; 4] = [
+    header::SET_COOKIE,
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::CONNECTION,
+];
+
+impl Shared {
+    /// 网关登录入口的完整地址，作为 CAS 的 service 参数
+    fn gateway_service(&self) -> String {
+        format!("{}{WEBVPN_LOGIN_PATH}", self.gateway_url)
+    }
+
+    /// 请求携带的有效 WebVPN 会话
+    fn vpn_session(&self, headers: &HeaderMap) -> Option<String> {
+        let id = cookie(headers, VPN_COOKIE)?;
+        self.lock()
+            .sessions
+            .vpn_sessions
+            .contains_key(id)
+            .then(|| id.to_string())
+    }
+}
+
+async fn gateway_index(State(shared): State<Arc<Shared>>, headers: HeaderMap) -> Response {
+    match shared.vpn_session(&headers) {
+        Some(_) => Html("<html><body>WebVPN</body></html>").into_response(),
+        None => redirect(WEBVPN_LOGIN_PATH),
+    }
+}
+
+/// 网关登录入口：没有票据时重定向到 CAS，携带有效票据时建立网关会话
+async fn gateway_login(State(shared): State<Arc<Shared>>, Query(query): QueryParams) -> Response {
+    let service = shared.gateway_service();
+    let Some(ticket) = query.get("ticket") else {
+        return redirect(&format!(
+            "{}/cas/login?service={}",
+            shared.base_url,
+            urlencoding::encode(&service)
+        ));
+    };
+    let mut state = shared.lock();
+    if state.sessions.tickets.remove(ticket) != Some(service) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let id = state.sessions.issue("vpn");
+    state
+        .sessions
+        .vpn_sessions
+        .insert(id.clone(), HashMap::new());
+    let set_cookie = format!("{VPN_COOKIE}={id}; Path=/");
+    ([(header::SET_COOKIE, set_cookie)], redirect("/")).into_response()
+}
+
+/// 转发 `/{scheme}/{加密的主机名}/{路径}` 形式的请求。所有校内主机都解析到本服务器，
+/// 校内服务的 cookie 保存在网关会话中，重定向地址改写回网关的形式。
+async fn gateway_proxy(State(shared): State<Arc<Shared>>, req: Request) -> Response {
+    let Some(vpn_session) = shared.vpn_session(req.headers()) else {
+        return redirect(WEBVPN_LOGIN_PATH);
+    };
+    let mut segments = req.uri().path().trim_start_matches('/').splitn(3, '/');
+    let (Some(scheme), Some(host), path) = (segments.next(), segments.next(), segments.next())
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(host) = webvpn::decrypt_host(host) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (scheme, port) = match scheme.split_once('-') {
+        Some((scheme, port)) => (scheme, format!(":{port}")),
+        None => (scheme, String::new()),
+    };
+    let query = req
+        .uri()
+        .query()
+        .map(|q| format!("?{q}"))
+        .unwrap_or_default();
+    let path = path.unwrap_or_default();
+    let target = format!("{scheme}://{host}{port}/{path}{query}");
+
+    let cookies = {
+        let mut state = shared.lock();
+        state.sessions.gateway_requests += 1;
+        state.sessions.vpn_sessions[&vpn_session]
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    let mut forwarded = shared
+        .gateway_client
+        .request(
+            req.method().clone(),
+            format!("{}/{path}{query}", shared.base_url),
+        )
+        .header(header::COOKIE, cookies);
+    for (name, value) in req.headers() {
+        if name != header::HOST && name != header::COOKIE && !HOP_HEADERS.contains(name) {
+            forwarded = forwarded.header(name, value);
+        }
+    }
+    let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+        .await
+        .unwrap_or_default();
+    let Ok(resp) = forwarded.body(body).send().await else {
+        return StatusCode::BAD_GATEWAY.into_response();
+    };
+
+    let mut response = Response::builder().status(resp.status().as_u16());
+    for (name, value) in resp.headers() {
+        if name == header::SET_COOKIE {
+            let pair = value.to_str().unwrap_or_default().split(';').next();
+            if let Some((name, value)) = pair.and_then(|p| p.split_once('=')) {
+                let mut state = shared.lock();
+                if let Some(jar) = state.sessions.vpn_sessions.get_mut(&vpn_session) {
+                    jar.insert(name.trim().to_string(), value.trim().to_string());
+                }
+            }
+        } else if name == header::LOCATION {
+            let location = value.to_str().unwrap_or_default();
+            let resolved = reqwest::Url::parse(&target)
+                .and_then(|base| base.join(location))
+                .map(|url| url.to_string())
+                .unwrap_or_else(|_| location.to_string());
+            response = response.header(
+                name,
+                webvpn::rewrite_url_with_origin(&shared.gateway_url, &resolved),
+            );
+        } else if !HOP_HEADERS.contains(name) {
+            response = response.header(name, value);
+        }
+    }
+    let body = resp.bytes().await.unwrap_or_default();
+    response
+        .body(axum::body::Body::from(body))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
@@ -0,0 +1,194 @@
+use crate::course::{Batch, CourseError, CourseType, TeachingClass, VolunteerOutcome};
+use crate::managed::ManagedCourseSession;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PlanError {
+    #[error("Plan file I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Plan file format error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Plan file format error: {0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+/// 选课计划：按顺序列出想选的课程，每门课程再按顺序列出偏好的教学班。
+///
+/// ```toml
+/// [[course]]
+/// course_number = "ECON2001"
+/// course_type = "TJKC"
+/// prefer = [{ teacher = "张老师" }, { class_id = "TC-3" }]
+///
+/// [[course]]
+/// course_number = "MATH1001"
+/// course_type = "TJKC"
+/// any = false
+/// prefer = [{ class_id = "TC-9" }]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Plan {
+    /// 按优先级排列的课程
+    #[serde(rename = "course", default)]
+    pub courses: Vec<PlannedCourse>,
+}
+
+/// 计划中的一门课程
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PlannedCourse {
+    /// 课程号
+    pub course_number: String,
+    pub course_type: CourseType,
+    /// 按顺序尝试的教学班
+    #[serde(default)]
+    pub prefer: Vec<Preference>,
+    /// 偏好的教学班都选不上时，是否选择其他有空位且不冲突的教学班
+    #[serde(default = "default_any")]
+    pub any: bool,
+}
+
+fn default_any() -> bool {
+    true
+}
+
+/// 对教学班的偏好
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preference {
+    /// 指定教学班 ID
+    ClassId(String),
+    /// 指定任课教师，教学班的教师名包含该名字即可
+    Teacher(String),
+}
+
+impl Preference {
+    fn matches(&self, class: &TeachingClass) -> bool {
+        match self {
+            Preference::ClassId(id) => class.teaching_class_id == *id,
+            Preference::Teacher(name) => class.teacher_name.contains(name.as_str()),
+        }
+    }
+}
+
+/// 一门课程的执行结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum PlanOutcome {
+    /// 选上了教学班，参数为教学班 ID
+    Obtained(String),
+    /// 执行前已经选了该课程的教学班
+    AlreadySelected(String),
+    /// 课程列表中没有这门课程
+    NotFound,
+    /// 没有选上，参数为尝试过的教学班及结果；没有可尝试的教学班时为空
+    Failed(Vec<(String, VolunteerOutcome)>),
+}
+
+/// 计划执行报告中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanResult {
+    pub course_number: String,
+    pub outcome: PlanOutcome,
+}
+
+impl Plan {
+    /// 读取计划文件，扩展名为 `.toml` 时按 TOML 解析，否则按 JSON 解析
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PlanError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            Ok(toml::from_str(&text)?)
+        } else {
+            Ok(serde_json::from_str(&text)?)
+        }
+    }
+
+    /// 按优先级依次处理每门课程。每门课程处理前重新查询课程列表，
+    /// 这样前面选上的课程造成的冲突会反映在后面课程的教学班上。
+    /// 会话中途过期时自动重新登录，不会中断计划。
+    pub async fn execute(
+        &self,
+        session: &ManagedCourseSession,
+        batch: &Batch,
+    ) -> Result<Vec<PlanResult>, CourseError> {
+        let mut results = Vec::with_capacity(self.courses.len());
+        for course in &self.courses {
+            let outcome = course.execute(session, batch).await?;
+            log::info!("Plan for {}: {outcome:?}", course.course_number);
+            results.push(PlanResult {
+                course_number: course.course_number.clone(),
+                outcome,
+            });
+        }
+        Ok(results)
+    }
+}
+
+impl PlannedCourse {
+    /// 按偏好顺序排列的候选教学班，已满或冲突的教学班不在其中
+    pub fn candidates<'a>(&self, classes: &'a [TeachingClass]) -> Vec<&'a TeachingClass> {
+        let available = |class: &&TeachingClass| !class.is_full && !class.is_conflict;
+        let mut candidates: Vec<&TeachingClass> = Vec::new();
+        for preference in &self.prefer {
+            for class in classes.iter().filter(available) {
+                if preference.matches(class)
+                    && !candidates
+                        .iter()
+                        .any(|c| c.teaching_class_id == class.teaching_class_id)
+                {
+                    candidates.push(class);
+                }
+            }
+        }
+        if self.any {
+            for class in classes.iter().filter(available) {
+                if !candidates
+                    .iter()
+                    .any(|c| c.teaching_class_id == class.teaching_class_id)
+                {
+                    candidates.push(class);
+                }
+            }
+        }
+        candidates
+    }
+
+    async fn execute(
+        &self,
+        session: &ManagedCourseSession,
+        batch: &Batch,
+    ) -> Result<PlanOutcome, CourseError> {
+        let courses = session
+            .list_course(batch, self.course_type, 0, &self.course_number)
+            .await?;
+        let Some(info) = courses
+            .iter()
+            .find(|c| c.course_number == self.course_number)
+        else {
+            return Ok(PlanOutcome::NotFound);
+        };
+        if let Some(chosen) = info.tc_list.iter().find(|c| c.is_choose) {
+            return Ok(PlanOutcome::AlreadySelected(
+                chosen.teaching_class_id.clone(),
+            ));
+        }
+        let mut attempts = Vec::new();
+        for class in self.candidates(&info.tc_list) {
+            let class_id = &class.teaching_class_id;
+            let outcome = session
+                .add_volunteer(batch, class_id, self.course_type)
+                .await?;
+            match outcome {
+                VolunteerOutcome::Success => return Ok(PlanOutcome::Obtained(class_id.clone())),
+                // 换一个教学班也无济于事
+                VolunteerOutcome::CreditLimitExceeded | VolunteerOutcome::NotInPeriod => {
+                    attempts.push((class_id.clone(), outcome));
+                    break;
+                }
+                outcome => attempts.push((class_id.clone(), outcome)),
+            }
+        }
+        Ok(PlanOutcome::Failed(attempts))
+    }
+}
@@ -0,0 +1,251 @@
+use crate::course::{Batch, CapacityInfo, CourseInfo, VolunteerOutcome};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// 对齐的表格，适合终端阅读
+    #[default]
+    Table,
+    /// 每行一条记录，字段以 ` - ` 分隔
+    Plain,
+    /// JSON，适合交给其他程序处理
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format: {s}")),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn renderer(self) -> Box<dyn Renderer> {
+        match self {
+            OutputFormat::Table => Box::new(TableRenderer),
+            OutputFormat::Plain => Box::new(PlainRenderer),
+            OutputFormat::Json => Box::new(JsonRenderer),
+        }
+    }
+}
+
+/// 将选课数据转换为给人或程序阅读的文本。库本身不输出任何内容，由调用方决定如何展示。
+pub trait Renderer {
+    fn batches(&self, batches: &[Batch]) -> String;
+
+    fn courses(&self, courses: &[CourseInfo]) -> String;
+
+    fn capacity(&self, class_id: &str, capacity: &CapacityInfo) -> String;
+
+    fn outcome(&self, class_id: &str, outcome: &VolunteerOutcome) -> String;
+}
+
+fn chosen(selected: bool) -> &'static str {
+    if selected { "已选" } else { "未选" }
+}
+
+/// 每行一条记录
+pub struct PlainRenderer;
+
+impl Renderer for PlainRenderer {
+    fn batches(&self, batches: &[Batch]) -> String {
+        batches
+            .iter()
+            .map(|b| {
+                format!(
+                    "{} - {} - {} ~ {}\n",
+                    b.code, b.name, b.begin_time, b.end_time
+                )
+            })
+            .collect()
+    }
+
+    fn courses(&self, courses: &[CourseInfo]) -> String {
+        let mut out = String::new();
+        for course in courses {
+            out.push_str(&format!(
+                "{} - {} - {}\n",
+                course.course_number,
+                course.course_name,
+                chosen(course.selected)
+            ));
+            for tc in &course.tc_list {
+                out.push_str(&format!(
+                    "  {} - {} - {} - {}/{} - {}\n",
+                    tc.teaching_class_id,
+                    tc.teacher_name,
+                    tc.teaching_place,
+                    tc.number_of_selected,
+                    tc.class_capacity,
+                    chosen(tc.is_choose)
+                ));
+            }
+        }
+        out
+    }
+
+    fn capacity(&self, class_id: &str, capacity: &CapacityInfo) -> String {
+        format!("{class_id} - {capacity}\n")
+    }
+
+    fn outcome(&self, class_id: &str, outcome: &VolunteerOutcome) -> String {
+        format!("{class_id} - {outcome}\n")
+    }
+}
+
+/// 终端中的显示宽度，中日韩字符和全角符号占两列
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF => 2,
+            0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// 按列对齐的表格，第一行为表头
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| display_width(h)).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+    let line = |cells: &mut dyn Iterator<Item = &str>| {
+        let mut out = String::new();
+        for (cell, width) in cells.zip(&widths) {
+            out.push_str(cell);
+            out.push_str(&" ".repeat(width - display_width(cell) + 2));
+        }
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        out
+    };
+    let mut out = line(&mut header.iter().copied());
+    out.push_str(&line(
+        &mut widths
+            .iter()
+            .map(|w| "-".repeat(*w))
+            .collect::<Vec<_>>()
+            .iter()
+            .map(String::as_str),
+    ));
+    for row in rows {
+        out.push_str(&line(&mut row.iter().map(String::as_str)));
+    }
+    out
+}
+
+/// 对齐的表格
+pub struct TableRenderer;
+
+impl Renderer for TableRenderer {
+    fn batches(&self, batches: &[Batch]) -> String {
+        let rows: Vec<Vec<String>> = batches
+            .iter()
+            .map(|b| {
+                vec![
+                    b.code.clone(),
+                    b.name.clone(),
+                    b.type_name.clone(),
+                    b.begin_time.clone(),
+                    b.end_time.clone(),
+                ]
+            })
+            .collect();
+        table(&["代码", "名称", "类型", "开始时间", "结束时间"], &rows)
+    }
+
+    fn courses(&self, courses: &[CourseInfo]) -> String {
+        let rows: Vec<Vec<String>> = courses
+            .iter()
+            .flat_map(|course| {
+                course.tc_list.iter().map(move |tc| {
+                    vec![
+                        course.course_number.clone(),
+                        course.course_name.clone(),
+                        tc.teaching_class_id.clone(),
+                        tc.teacher_name.clone(),
+                        tc.teaching_place.clone(),
+                        format!("{}/{}", tc.number_of_selected, tc.class_capacity),
+                        chosen(tc.is_choose).to_string(),
+                    ]
+                })
+            })
+            .collect();
+        table(
+            &[
+                "课程号",
+                "课程名",
+                "教学班",
+                "教师",
+                "时间地点",
+                "人数",
+                "状态",
+            ],
+            &rows,
+        )
+    }
+
+    fn capacity(&self, class_id: &str, capacity: &CapacityInfo) -> String {
+        let row = vec![
+            class_id.to_string(),
+            format!("{}/{}", capacity.number_of_male, capacity.capacity_of_male),
+            format!(
+                "{}/{}",
+                capacity.number_of_female, capacity.capacity_of_female
+            ),
+            format!(
+                "{}/{}",
+                capacity.number_of_selected, capacity.class_capacity
+            ),
+        ];
+        table(&["教学班", "男生", "女生", "总计"], &[row])
+    }
+
+    fn outcome(&self, class_id: &str, outcome: &VolunteerOutcome) -> String {
+        table(
+            &["教学班", "结果"],
+            &[vec![class_id.to_string(), outcome.to_string()]],
+        )
+    }
+}
+
+/// JSON，每次调用输出一个文档
+pub struct JsonRenderer;
+
+impl JsonRenderer {
+    fn json(value: &impl Serialize) -> String {
+        let mut out = serde_json::to_string_pretty(value)
+            .unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }).to_string());
+        out.push('\n');
+        out
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn batches(&self, batches: &[Batch]) -> String {
+        Self::json(&batches)
+    }
+
+    fn courses(&self, courses: &[CourseInfo]) -> String {
+        Self::json(&courses)
+    }
+
+    fn capacity(&self, class_id: &str, capacity: &CapacityInfo) -> String {
+        Self::json(&serde_json::json!({ "classId": class_id, "capacity": capacity }))
+    }
+
+    fn outcome(&self, class_id: &str, outcome: &VolunteerOutcome) -> String {
+        Self::json(&serde_json::json!({ "classId": class_id, "outcome": outcome }))
+    }
+}
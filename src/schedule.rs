@@ -0,0 +1,232 @@
+use crate::course::{Batch, CourseError, VolunteerOutcome};
+use crate::managed::ManagedCourseSession;
+use crate::sniper::Target;
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use futures_util::future::join_all;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// 本地时钟与选课系统时钟的偏差估计
+#[derive(Debug, Clone, Copy)]
+pub struct ClockSync {
+    /// 服务器时间减去本地时间
+    pub offset: TimeDelta,
+    /// 偏差估计的误差范围（±）
+    pub uncertainty: Duration,
+    /// 最小的往返耗时
+    pub rtt: Duration,
+}
+
+impl ClockSync {
+    /// 服务器时间对应的本地时间
+    pub fn to_local(&self, server: DateTime<FixedOffset>) -> DateTime<Utc> {
+        server.with_timezone(&Utc) - self.offset
+    }
+}
+
+/// 多次请求选课系统，根据响应头 `Date` 和往返耗时估计时钟偏差。
+///
+/// `Date` 只精确到秒：服务器处理请求时的真实时间落在 `[Date, Date + 1s)` 内，
+/// 且发生在本地发出请求和收到响应之间，所以每次采样都给出偏差的一个区间。
+/// 采样间隔不是整秒时，各次采样落在秒内的不同位置，区间的交集会明显变窄。
+pub async fn sync_clock(
+    session: &ManagedCourseSession,
+    samples: u32,
+    spacing: Duration,
+) -> Result<ClockSync, CourseError> {
+    let second = TimeDelta::seconds(1);
+    let mut bounds: Option<(TimeDelta, TimeDelta)> = None;
+    let mut last = None;
+    let mut rtt = Duration::MAX;
+    for i in 0..samples.max(1) {
+        if i > 0 {
+            sleep(spacing).await;
+        }
+        let sent = Utc::now();
+        let date = session.server_date().await?;
+        let received = Utc::now();
+        rtt = rtt.min((received - sent).to_std().unwrap_or_default());
+        let (low, high) = (date - received, date + second - sent);
+        last = Some((low, high));
+        bounds = match bounds {
+            Some((l, h)) => Some((l.max(low), h.min(high))),
+            None => Some((low, high)),
+        };
+    }
+    let (mut low, mut high) = bounds.expect("at least one sample");
+    if low > high {
+        // 区间没有交集，说明采样期间某一方的时钟发生了跳变，只采用最后一次
+        log::warn!("Inconsistent clock samples, using the last one");
+        (low, high) = last.expect("at least one sample");
+    }
+    let clock = ClockSync {
+        offset: low + (high - low) / 2,
+        uncertainty: ((high - low) / 2).to_std().unwrap_or_default(),
+        rtt,
+    };
+    log::info!(
+        "Server clock offset {}ms (±{}ms), rtt {}ms",
+        clock.offset.num_milliseconds(),
+        clock.uncertainty.as_millis(),
+        clock.rtt.as_millis()
+    );
+    Ok(clock)
+}
+
+/// 定时选课参数
+#[derive(Debug, Clone)]
+pub struct ScheduleConfig {
+    /// 提前多久开始准备：检查会话、建立连接、校准时钟
+    pub prewarm: Duration,
+    /// 校准时钟的采样次数
+    pub clock_samples: u32,
+    /// 采样间隔，不宜是整秒
+    pub sample_spacing: Duration,
+    /// 在预计开放时刻之前多久发出第一轮请求，用于抵消估计误差
+    pub lead: Duration,
+    /// 开放后持续重试多久
+    pub burst_window: Duration,
+    /// 每轮重试之间的间隔
+    pub retry_interval: Duration,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            prewarm: Duration::from_secs(30),
+            clock_samples: 8,
+            sample_spacing: Duration::from_millis(130),
+            lead: Duration::ZERO,
+            burst_window: Duration::from_secs(5),
+            retry_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// 定时选课结果
+#[derive(Debug, Clone)]
+pub struct ScheduleReport {
+    pub clock: ClockSync,
+    /// 第一轮请求发出的时间
+    pub fired_at: DateTime<Utc>,
+    /// 请求轮数
+    pub rounds: u32,
+    /// 已选上的教学班 ID
+    pub obtained: Vec<String>,
+    /// 因时间冲突、学分上限、已选同一课程的其他教学班等原因放弃的教学班及选课结果
+    pub abandoned: Vec<(String, VolunteerOutcome)>,
+    /// 重试期结束时仍未选上的教学班
+    pub missed: Vec<String>,
+}
+
+/// 在批次开放的时刻发出准备好的选课请求，开放后的一段时间内持续重试
+pub struct Scheduler<'a> {
+    session: &'a ManagedCourseSession,
+    batch: &'a Batch,
+    targets: Vec<Target>,
+    config: ScheduleConfig,
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new(session: &'a ManagedCourseSession, batch: &'a Batch, targets: Vec<Target>) -> Self {
+        Self {
+            session,
+            batch,
+            targets,
+            config: ScheduleConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: ScheduleConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// 等待批次开放并选课。批次已经开放时立即开始。
+    pub async fn run(&self) -> Result<ScheduleReport, CourseError> {
+        let opens_at = self.batch.begins_at()?;
+        let prewarm = TimeDelta::from_std(self.config.prewarm).unwrap_or(TimeDelta::MAX);
+        log::info!("Batch {} opens at {opens_at}", self.batch.code);
+        // 此时还不知道时钟偏差，按本地时钟等待，偏差由预热时间吸收
+        // 预热时间过长、超出时间范围时立即开始
+        if let Some(time) = opens_at.with_timezone(&Utc).checked_sub_signed(prewarm) {
+            sleep_until(time).await;
+        }
+
+        // 预热：过期的会话在这里重新登录，同时建立到选课系统的连接
+        self.session.get_batch_list().await?;
+        let clock = sync_clock(
+            self.session,
+            self.config.clock_samples,
+            self.config.sample_spacing,
+        )
+        .await?;
+        let advance = TimeDelta::from_std((clock.rtt / 2).saturating_add(self.config.lead))
+            .unwrap_or(TimeDelta::MAX);
+        if let Some(time) = clock.to_local(opens_at).checked_sub_signed(advance) {
+            sleep_until(time).await;
+        }
+
+        let fired_at = Utc::now();
+        let deadline = tokio::time::Instant::now() + self.config.burst_window;
+        let mut report = ScheduleReport {
+            clock,
+            fired_at,
+            rounds: 0,
+            obtained: Vec::new(),
+            abandoned: Vec::new(),
+            missed: Vec::new(),
+        };
+        let mut pending = self.targets.clone();
+        while !pending.is_empty() {
+            report.rounds += 1;
+            let results = join_all(pending.iter().map(|target| {
+                self.session
+                    .add_volunteer(self.batch, &target.class_id, target.course_type)
+            }))
+            .await;
+            let mut retry = Vec::new();
+            for (target, result) in pending.into_iter().zip(results) {
+                let result = match result {
+                    Ok(outcome) => target.confirm(self.session, self.batch, outcome).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(VolunteerOutcome::Success) => {
+                        log::info!("Obtained teaching class {}", target.class_id);
+                        report.obtained.push(target.class_id);
+                    }
+                    Ok(
+                        outcome @ (VolunteerOutcome::Conflict
+                        | VolunteerOutcome::CreditLimitExceeded
+                        | VolunteerOutcome::AlreadySelected),
+                    ) => {
+                        log::warn!("Giving up teaching class {}: {outcome}", target.class_id);
+                        report.abandoned.push((target.class_id, outcome));
+                    }
+                    Ok(_) => retry.push(target),
+                    // 刚开放时服务器繁忙，可能返回错误页面或超时
+                    Err(e) if e.is_retryable() => {
+                        log::warn!("Request for {} failed: {e}", target.class_id);
+                        retry.push(target)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            pending = retry;
+            if pending.is_empty() || tokio::time::Instant::now() >= deadline {
+                break;
+            }
+            sleep(self.config.retry_interval).await;
+        }
+        report.missed = pending.into_iter().map(|t| t.class_id).collect();
+        Ok(report)
+    }
+}
+
+/// 按本地时钟等待到 `time`，已经过去则立即返回
+async fn sleep_until(time: DateTime<Utc>) {
+    if let Ok(delay) = (time - Utc::now()).to_std() {
+        sleep(delay).await;
+    }
+}
@@ -0,0 +1,322 @@
+use crate::endpoints::Endpoints;
+use crate::login::{LoginError, follow_redirects, follow_until, is_cas_login_url};
+use reqwest::{Client, Response, StatusCode};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// CAS 登录页的路径
+pub static CAS_LOGIN_PATH: &str = "/cas/login";
+
+/// trait 中的异步方法需要支持 `dyn`，因此返回装箱的 future
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// 登录过程中提供给服务的上下文
+pub struct ServiceContext<'a> {
+    /// 登录使用的客户端，cookie 会保留到登录结果中
+    pub client: &'a Client,
+    /// 是否经由 WebVPN 访问
+    pub webvpn: bool,
+    /// 各服务的地址
+    pub endpoints: &'a Endpoints,
+}
+
+impl ServiceContext<'_> {
+    /// 按访问方式改写 URL
+    pub fn url(&self, url: &str) -> String {
+        self.endpoints.route(url, self.webvpn)
+    }
+
+    /// 沿重定向链前进，直到遇到非重定向响应
+    pub async fn follow_redirects(&self, url: &str) -> Result<Response, LoginError> {
+        follow_redirects(self.client, url, None).await
+    }
+
+    /// 沿重定向链前进，返回 CAS 登录页的地址，但不请求它。
+    /// 已有 CAS 会话时，请求 CAS 登录页就会颁发一张票据并重定向回服务，因此只能请求一次，留给登录流程。
+    /// 重定向链没有经过 CAS 时返回最终到达的地址。
+    pub async fn follow_to_cas(&self, url: &str) -> Result<String, LoginError> {
+        follow_until(self.client, url, &is_cas_login_url).await
+    }
+}
+
+/// 以 login.xjtu.edu.cn 为认证中心的服务。实现该 trait 即可用 [`crate::login::login`] 登录自定义服务。
+pub trait CasService: Send + Sync {
+    /// 服务名称，用于日志
+    fn name(&self) -> &str;
+
+    /// 用于探测能否直接访问该服务的地址
+    fn probe_url(&self, endpoints: &Endpoints) -> String;
+
+    /// 找到该服务的 CAS 登录页地址（包含 `service=` 参数），经由 WebVPN 时应返回改写后的地址。
+    /// 可以请求服务入口来发现地址，但不应请求 CAS 登录页本身，见 [`ServiceContext::follow_to_cas`]。
+    fn login_url<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+    ) -> BoxFuture<'a, Result<String, LoginError>>;
+
+    /// 提交 CAS 表单后沿重定向链前进，遇到满足条件的响应即停止。默认跟随到第一个非重定向响应。
+    fn is_landed(&self, _resp: &Response) -> bool {
+        false
+    }
+
+    /// 检查停下来的响应是否表示登录成功，默认要求状态码为 200
+    fn check_landing(&self, landing: &Response) -> Result<(), LoginError> {
+        if landing.status() == StatusCode::OK {
+            Ok(())
+        } else {
+            Err(LoginError::UnexpectedStatus(
+                landing.url().to_string(),
+                landing.status(),
+            ))
+        }
+    }
+
+    /// 登录成功后的处理，例如换取服务自己的 token。默认什么也不做。
+    fn after_login<'a>(
+        &'a self,
+        _ctx: &'a ServiceContext<'a>,
+        _landing: &'a Response,
+    ) -> BoxFuture<'a, Result<(), LoginError>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl<T: CasService + ?Sized> CasService for &T {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn probe_url(&self, endpoints: &Endpoints) -> String {
+        (**self).probe_url(endpoints)
+    }
+
+    fn login_url<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+    ) -> BoxFuture<'a, Result<String, LoginError>> {
+        (**self).login_url(ctx)
+    }
+
+    fn is_landed(&self, resp: &Response) -> bool {
+        (**self).is_landed(resp)
+    }
+
+    fn check_landing(&self, landing: &Response) -> Result<(), LoginError> {
+        (**self).check_landing(landing)
+    }
+
+    fn after_login<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+        landing: &'a Response,
+    ) -> BoxFuture<'a, Result<(), LoginError>> {
+        (**self).after_login(ctx, landing)
+    }
+}
+
+impl<T: CasService + ?Sized> CasService for Arc<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn probe_url(&self, endpoints: &Endpoints) -> String {
+        (**self).probe_url(endpoints)
+    }
+
+    fn login_url<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+    ) -> BoxFuture<'a, Result<String, LoginError>> {
+        (**self).login_url(ctx)
+    }
+
+    fn is_landed(&self, resp: &Response) -> bool {
+        (**self).is_landed(resp)
+    }
+
+    fn check_landing(&self, landing: &Response) -> Result<(), LoginError> {
+        (**self).check_landing(landing)
+    }
+
+    fn after_login<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+        landing: &'a Response,
+    ) -> BoxFuture<'a, Result<(), LoginError>> {
+        (**self).after_login(ctx, landing)
+    }
+}
+
+/// AI 平台
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AiPlatform;
+
+impl CasService for AiPlatform {
+    fn name(&self) -> &str {
+        "AI 平台"
+    }
+
+    fn probe_url(&self, endpoints: &Endpoints) -> String {
+        endpoints.ai_url("/")
+    }
+
+    fn login_url<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+    ) -> BoxFuture<'a, Result<String, LoginError>> {
+        Box::pin(async move {
+            let login_start: serde_json::Value = ctx
+                .client
+                .post(ctx.url(&ctx.endpoints.ai_url("/api/auth/login")))
+                .json(&serde_json::json!(  {"SSO":"Oauth","IdpID":"1","RedirectUrl":"/"}))
+                .send()
+                .await
+                .map_err(LoginError::RequestError)?
+                .json()
+                .await
+                .map_err(LoginError::RequestError)?;
+            if let serde_json::Value::Object(obj) = &login_start {
+                if let Some(serde_json::Value::String(url)) = obj.get("redirect_uri") {
+                    Ok(ctx.url(url))
+                } else {
+                    Err(LoginError::Other(format!(
+                        "No url found in login start response: {login_start}"
+                    )))
+                }
+            } else {
+                Err(LoginError::Other(format!(
+                    "Unexpected login start response: {login_start}"
+                )))
+            }
+        })
+    }
+
+    fn is_landed(&self, r: &Response) -> bool {
+        r.status() != StatusCode::FOUND // not 302
+            || r.headers().get("Location") // success
+                .and_then(|loc| loc.to_str().ok().map(|s| s.contains("/login-success")))
+                .unwrap_or(false)
+    }
+
+    fn check_landing(&self, landing: &Response) -> Result<(), LoginError> {
+        if landing.status() != StatusCode::FOUND {
+            return Err(LoginError::ExpectedRedirect(
+                landing.url().as_str().to_string(),
+                landing.status(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 选课系统
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CourseSelection;
+
+impl CasService for CourseSelection {
+    fn name(&self) -> &str {
+        "选课系统"
+    }
+
+    fn probe_url(&self, endpoints: &Endpoints) -> String {
+        endpoints.course_url("/")
+    }
+
+    fn login_url<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+    ) -> BoxFuture<'a, Result<String, LoginError>> {
+        Box::pin(async move {
+            ctx.follow_to_cas(
+                &ctx.url(
+                    &ctx.endpoints
+                        .course_url("/xsxkapp/sys/xsxkapp/*default/index.do"),
+                ),
+            )
+            .await
+        })
+    }
+}
+
+/// 任意 CAS 客户端，只知道它在 CAS 登录地址中的 `service=` 参数。
+///
+/// 这类服务验证票据之后的行为各不相同（重定向到其他域名、返回 403、反复重定向等），
+/// 因此携带票据请求一次回调地址后即停止，不论响应如何都视为成功，以免丢掉 CAS 已经颁发的票据。
+#[derive(Debug, Clone)]
+pub struct ServiceUrl {
+    url: String,
+}
+
+impl ServiceUrl {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+}
+
+impl CasService for ServiceUrl {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn probe_url(&self, _endpoints: &Endpoints) -> String {
+        self.url.clone()
+    }
+
+    fn login_url<'a>(
+        &'a self,
+        ctx: &'a ServiceContext<'a>,
+    ) -> BoxFuture<'a, Result<String, LoginError>> {
+        Box::pin(async move {
+            Ok(ctx.url(&format!(
+                "{}?service={}",
+                ctx.endpoints.cas_url(CAS_LOGIN_PATH),
+                urlencoding::encode(&self.url)
+            )))
+        })
+    }
+
+    fn is_landed(&self, _resp: &Response) -> bool {
+        true
+    }
+
+    fn check_landing(&self, _landing: &Response) -> Result<(), LoginError> {
+        Ok(())
+    }
+}
+
+/// 按名称登记的服务，便于下游按配置选择要登录的服务
+#[derive(Default, Clone)]
+pub struct ServiceRegistry {
+    services: HashMap<String, Arc<dyn CasService>>,
+}
+
+impl ServiceRegistry {
+    /// 空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 包含内置服务的注册表，键为 `ai` 和 `course`
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register("ai", AiPlatform);
+        registry.register("course", CourseSelection);
+        registry
+    }
+
+    /// 登记服务，同名的旧服务会被替换
+    pub fn register(&mut self, key: impl Into<String>, service: impl CasService + 'static) {
+        self.services.insert(key.into(), Arc::new(service));
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<dyn CasService>> {
+        self.services.get(key).cloned()
+    }
+
+    /// 已登记的服务名
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.services.keys().map(String::as_str)
+    }
+}
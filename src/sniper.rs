@@ -0,0 +1,236 @@
+use crate::catalog::Catalog;
+use crate::course::{Batch, CourseError, CourseType, Gender, VolunteerOutcome};
+use crate::managed::ManagedCourseSession;
+use rand::Rng;
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+
+/// 要蹲的教学班
+#[derive(Debug, Clone)]
+pub struct Target {
+    /// 教学班 ID
+    pub class_id: String,
+    pub course_type: CourseType,
+}
+
+impl Target {
+    pub fn new(class_id: impl Into<String>, course_type: CourseType) -> Self {
+        Self {
+            class_id: class_id.into(),
+            course_type,
+        }
+    }
+
+    /// 同一课程已选了其他教学班时，选课系统同样返回 [`VolunteerOutcome::AlreadySelected`]。
+    /// 这时到课程列表中确认：已选的正是该教学班才视为成功，否则原样返回。
+    pub(crate) async fn confirm(
+        &self,
+        session: &ManagedCourseSession,
+        batch: &Batch,
+        outcome: VolunteerOutcome,
+    ) -> Result<VolunteerOutcome, CourseError> {
+        if outcome != VolunteerOutcome::AlreadySelected {
+            return Ok(outcome);
+        }
+        let courses = Catalog::new(session, batch, self.course_type, "")
+            .fetch_all()
+            .await?;
+        let chosen = courses
+            .iter()
+            .flat_map(|c| &c.tc_list)
+            .any(|c| c.teaching_class_id == self.class_id && c.is_choose);
+        Ok(if chosen {
+            VolunteerOutcome::Success
+        } else {
+            outcome
+        })
+    }
+}
+
+/// 抢到哪些教学班后停止
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StopWhen {
+    /// 抢到任意一个即停止，适用于同一门课的多个备选教学班
+    #[default]
+    Any,
+    /// 所有目标都抢到（或放弃）后停止
+    All,
+}
+
+/// 蹲课参数
+#[derive(Debug, Clone)]
+pub struct SniperConfig {
+    /// 两轮查询之间的基础间隔
+    pub interval: Duration,
+    /// 在基础间隔上随机增加 `0..=jitter`，避免请求过于规律
+    pub jitter: Duration,
+    /// 学生性别，用于判断分性别的课容量，见 [`crate::course::CapacityInfo::has_seat`]
+    pub gender: Option<Gender>,
+    pub stop_when: StopWhen,
+    /// 最长蹲课时间
+    pub timeout: Option<Duration>,
+    /// 最多查询轮数
+    pub max_rounds: Option<u32>,
+}
+
+impl Default for SniperConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            jitter: Duration::from_millis(300),
+            gender: None,
+            stop_when: StopWhen::default(),
+            timeout: None,
+            max_rounds: None,
+        }
+    }
+}
+
+/// 停止的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// 满足了 [`StopWhen`]
+    Obtained,
+    /// 所有未抢到的目标都已放弃
+    Exhausted,
+    Timeout,
+    MaxRounds,
+}
+
+/// 蹲课结果
+#[derive(Debug, Clone)]
+pub struct SnipeReport {
+    /// 已选上的教学班 ID
+    pub obtained: Vec<String>,
+    /// 因时间冲突、学分上限等原因放弃的教学班及选课结果
+    pub abandoned: Vec<(String, VolunteerOutcome)>,
+    /// 查询轮数
+    pub rounds: u32,
+    pub reason: StopReason,
+}
+
+/// 轮询教学班容量，出现空位时立即添加志愿。会话失效时由 [`ManagedCourseSession`] 自动重新登录。
+pub struct Sniper<'a> {
+    session: &'a ManagedCourseSession,
+    batch: &'a Batch,
+    targets: Vec<Target>,
+    config: SniperConfig,
+}
+
+impl<'a> Sniper<'a> {
+    pub fn new(session: &'a ManagedCourseSession, batch: &'a Batch, targets: Vec<Target>) -> Self {
+        Self {
+            session,
+            batch,
+            targets,
+            config: SniperConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: SniperConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// 开始蹲课，直到满足停止条件。网络错误、维护页面等暂时的错误（见 [`CourseError::is_retryable`]）
+    /// 只记录日志，下一轮继续；其他错误直接返回。
+    pub async fn run(&self) -> Result<SnipeReport, CourseError> {
+        let started = Instant::now();
+        let mut pending = self.targets.clone();
+        let mut report = SnipeReport {
+            obtained: Vec::new(),
+            abandoned: Vec::new(),
+            rounds: 0,
+            reason: StopReason::Exhausted,
+        };
+        loop {
+            report.rounds += 1;
+            let mut index = 0;
+            while index < pending.len() {
+                let target = &pending[index];
+                match self.try_target(target).await {
+                    Ok(None) => index += 1,
+                    Ok(Some(outcome)) => {
+                        let target = pending.remove(index);
+                        if outcome.is_success() {
+                            log::info!("Obtained teaching class {}", target.class_id);
+                            report.obtained.push(target.class_id);
+                            if self.config.stop_when == StopWhen::Any {
+                                report.reason = StopReason::Obtained;
+                                return Ok(report);
+                            }
+                        } else {
+                            log::warn!("Giving up teaching class {}: {outcome}", target.class_id);
+                            report.abandoned.push((target.class_id, outcome));
+                        }
+                    }
+                    Err(e) if e.is_retryable() => {
+                        log::warn!("Request for {} failed: {e}", target.class_id);
+                        index += 1;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if pending.is_empty() {
+                report.reason = if report.obtained.is_empty() {
+                    StopReason::Exhausted
+                } else {
+                    StopReason::Obtained
+                };
+                return Ok(report);
+            }
+            if self
+                .config
+                .max_rounds
+                .is_some_and(|max| report.rounds >= max)
+            {
+                report.reason = StopReason::MaxRounds;
+                return Ok(report);
+            }
+            let delay = self.delay();
+            if let Some(timeout) = self.config.timeout
+                && started.elapsed() + delay >= timeout
+            {
+                report.reason = StopReason::Timeout;
+                return Ok(report);
+            }
+            sleep(delay).await;
+        }
+    }
+
+    /// 检查一个目标，有空位时添加志愿。返回 `None` 表示仍需继续蹲，
+    /// 否则返回最终的选课结果（选上或应当放弃）。
+    async fn try_target(&self, target: &Target) -> Result<Option<VolunteerOutcome>, CourseError> {
+        let capacity = self.session.get_capacity(&target.class_id).await?;
+        if !capacity.has_seat(self.config.gender) {
+            log::debug!("{} is full: {capacity}", target.class_id);
+            return Ok(None);
+        }
+        log::info!("Seat available in {}: {capacity}", target.class_id);
+        let outcome = self
+            .session
+            .add_volunteer(self.batch, &target.class_id, target.course_type)
+            .await?;
+        let outcome = target.confirm(self.session, self.batch, outcome).await?;
+        Ok(match outcome {
+            // 空位被别人抢先占用，或选课尚未开放，继续等待
+            VolunteerOutcome::Full | VolunteerOutcome::NotInPeriod => None,
+            VolunteerOutcome::Unknown(ref message) => {
+                log::warn!("Unexpected result for {}: {message}", target.class_id);
+                None
+            }
+            outcome => Some(outcome),
+        })
+    }
+
+    fn delay(&self) -> Duration {
+        let jitter = self.config.jitter.as_millis() as u64;
+        let extra = if jitter == 0 {
+            0
+        } else {
+            rand::thread_rng().gen_range(0..=jitter)
+        };
+        self.config.interval + Duration::from_millis(extra)
+    }
+}
@@ -0,0 +1,279 @@
+use crate::course::{CourseInfo, CourseType, TeachingClass};
+use crate::sniper::Target;
+use crate::timetable::{Schedule, Timetable, Weeks};
+use serde::Serialize;
+
+/// 排课偏好，每项是一个权重，为 0 时不考虑该项
+#[derive(Debug, Clone)]
+pub struct Preferences {
+    /// 偏好的教师，教学班的教师名包含其中任意一个即可
+    pub teachers: Vec<String>,
+    /// 每个偏好教师的教学班的加分
+    pub teacher_bonus: f64,
+    /// 平均每周第 1 节就有课的天数的扣分
+    pub early_penalty: f64,
+    /// 平均每周同一天两次课之间空闲节数的扣分
+    pub gap_penalty: f64,
+    /// 平均每周有课天数的扣分
+    pub day_penalty: f64,
+    /// 每个已满教学班的扣分
+    pub full_penalty: f64,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            teachers: Vec::new(),
+            teacher_bonus: 10.0,
+            early_penalty: 2.0,
+            gap_penalty: 1.0,
+            day_penalty: 1.0,
+            full_penalty: 20.0,
+        }
+    }
+}
+
+/// 方案中的一个教学班
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Choice {
+    pub course_number: String,
+    pub course_name: String,
+    pub course_type: CourseType,
+    pub class_id: String,
+    pub teacher_name: String,
+}
+
+/// 一个没有时间冲突的选课方案
+#[derive(Debug, Clone, Serialize)]
+pub struct Solution {
+    /// 与加入课程的顺序相同
+    pub choices: Vec<Choice>,
+    /// 分数越高越符合偏好
+    pub score: f64,
+}
+
+impl Solution {
+    /// 可以直接交给 [`crate::sniper::Sniper`] 或 [`crate::schedule::Scheduler`] 的目标
+    pub fn targets(&self) -> Vec<Target> {
+        self.choices
+            .iter()
+            .map(|c| Target::new(&c.class_id, c.course_type))
+            .collect()
+    }
+}
+
+struct Candidate<'a> {
+    class: &'a TeachingClass,
+    schedule: Schedule,
+}
+
+struct Course<'a> {
+    info: &'a CourseInfo,
+    course_type: CourseType,
+}
+
+/// 枚举每门课程各选一个教学班、且相互之间没有时间冲突的组合，按偏好排序
+pub struct Solver<'a> {
+    courses: Vec<Course<'a>>,
+    fixed: Timetable,
+    preferences: Preferences,
+    max_combinations: usize,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(preferences: Preferences) -> Self {
+        Self {
+            courses: Vec::new(),
+            fixed: Timetable::new(),
+            preferences,
+            max_combinations: 1_000_000,
+        }
+    }
+
+    /// 加入一门想选的课程，候选教学班为它的 `tc_list`
+    pub fn add_course(&mut self, course: &'a CourseInfo, course_type: CourseType) {
+        self.courses.push(Course {
+            info: course,
+            course_type,
+        });
+    }
+
+    /// 已经确定的课表（如已选的其他课程），与之冲突的教学班不参与组合
+    pub fn with_timetable(mut self, timetable: Timetable) -> Self {
+        self.fixed = timetable;
+        self
+    }
+
+    /// 最多评估多少个完整组合，超出后停止枚举
+    pub fn with_max_combinations(mut self, max: usize) -> Self {
+        self.max_combinations = max;
+        self
+    }
+
+    /// 分数最高的至多 `top` 个方案。无法解析上课安排的教学班视为不与任何课程冲突。
+    pub fn solve(&self, top: usize) -> Vec<Solution> {
+        let mut candidates: Vec<Vec<Candidate>> = self
+            .courses
+            .iter()
+            .map(|course| {
+                course
+                    .info
+                    .tc_list
+                    .iter()
+                    .map(|class| Candidate {
+                        class,
+                        schedule: class.schedule(),
+                    })
+                    .filter(|c| {
+                        self.fixed
+                            .conflicts_with(&c.class.teaching_class_id, &c.schedule)
+                            .is_empty()
+                    })
+                    .collect()
+            })
+            .collect();
+        // 先安排候选少的课程，尽早剪枝；结果中仍按加入的顺序排列
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by_key(|i| candidates[*i].len());
+        let sorted: Vec<Vec<Candidate>> = order
+            .iter()
+            .map(|i| std::mem::take(&mut candidates[*i]))
+            .collect();
+
+        let mut search = Search {
+            solver: self,
+            candidates: &sorted,
+            order: &order,
+            picked: Vec::with_capacity(sorted.len()),
+            best: Vec::new(),
+            top,
+            evaluated: 0,
+        };
+        if top > 0 {
+            search.descend();
+        }
+        if search.evaluated >= self.max_combinations {
+            log::warn!(
+                "Stopped after {} combinations, results may be incomplete",
+                search.evaluated
+            );
+        }
+        search.best
+    }
+
+    fn score(&self, picked: &[&Candidate]) -> f64 {
+        let prefs = &self.preferences;
+        let mut score = 0.0;
+        for candidate in picked {
+            if prefs
+                .teachers
+                .iter()
+                .any(|t| candidate.class.teacher_name.contains(t.as_str()))
+            {
+                score += prefs.teacher_bonus;
+            }
+            if candidate.class.is_full {
+                score -= prefs.full_penalty;
+            }
+        }
+
+        // 每周每天占用的节次，按周平均，使单双周的课程与每周都上的课程可比
+        let mut active = Weeks::default();
+        let (mut days, mut early, mut gaps) = (0u32, 0u32, 0u32);
+        for week in 1..=Weeks::MAX {
+            let mut periods = [0u32; 7];
+            for meeting in picked.iter().flat_map(|c| &c.schedule.meetings) {
+                if meeting.weeks.contains(week) {
+                    let day = meeting.weekday.num_days_from_monday() as usize;
+                    for period in meeting.first_period..=meeting.last_period.min(31) {
+                        periods[day] |= 1 << period;
+                    }
+                }
+            }
+            for mask in periods.into_iter().filter(|m| *m != 0) {
+                active.insert(week);
+                days += 1;
+                if mask & 0b10 != 0 {
+                    early += 1;
+                }
+                let span = (31 - mask.leading_zeros()) - mask.trailing_zeros() + 1;
+                gaps += span - mask.count_ones();
+            }
+        }
+        let weeks = f64::from(active.len().max(1));
+        score -= prefs.day_penalty * f64::from(days) / weeks;
+        score -= prefs.early_penalty * f64::from(early) / weeks;
+        score -= prefs.gap_penalty * f64::from(gaps) / weeks;
+        score
+    }
+}
+
+struct Search<'s, 'a> {
+    solver: &'s Solver<'a>,
+    /// 按搜索顺序排列的候选
+    candidates: &'s [Vec<Candidate<'a>>],
+    /// 搜索顺序中第 i 门课程在加入顺序中的位置
+    order: &'s [usize],
+    picked: Vec<&'s Candidate<'a>>,
+    best: Vec<Solution>,
+    top: usize,
+    evaluated: usize,
+}
+
+impl Search<'_, '_> {
+    fn descend(&mut self) {
+        if self.evaluated >= self.solver.max_combinations {
+            return;
+        }
+        let depth = self.picked.len();
+        if depth == self.candidates.len() {
+            self.evaluated += 1;
+            self.record();
+            return;
+        }
+        for candidate in &self.candidates[depth] {
+            if self
+                .picked
+                .iter()
+                .any(|p| p.schedule.conflicts_with(&candidate.schedule))
+            {
+                continue;
+            }
+            self.picked.push(candidate);
+            self.descend();
+            self.picked.pop();
+        }
+    }
+
+    fn record(&mut self) {
+        let score = self.solver.score(&self.picked);
+        if self.best.len() == self.top && self.best.last().is_some_and(|w| w.score >= score) {
+            return;
+        }
+        let mut choices: Vec<(usize, Choice)> = self
+            .picked
+            .iter()
+            .enumerate()
+            .map(|(depth, candidate)| {
+                let course = &self.solver.courses[self.order[depth]];
+                let choice = Choice {
+                    course_number: course.info.course_number.clone(),
+                    course_name: course.info.course_name.clone(),
+                    course_type: course.course_type,
+                    class_id: candidate.class.teaching_class_id.clone(),
+                    teacher_name: candidate.class.teacher_name.clone(),
+                };
+                (self.order[depth], choice)
+            })
+            .collect();
+        choices.sort_by_key(|(index, _)| *index);
+        let solution = Solution {
+            choices: choices.into_iter().map(|(_, c)| c).collect(),
+            score,
+        };
+        // 分数相同时先找到的方案在前
+        let position = self.best.partition_point(|s| s.score >= score);
+        self.best.insert(position, solution);
+        self.best.truncate(self.top);
+    }
+}
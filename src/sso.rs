@@ -0,0 +1,115 @@
+use crate::endpoints::Endpoints;
+use crate::login::{
+    self, Authenticated, LoginError, LoginOptions, LoginSuccess, ServiceTicket, build_client,
+    cas_login, expect_redirect, is_cas_login_url, login_webvpn, use_webvpn,
+};
+use crate::service::{CAS_LOGIN_PATH, CasService, ServiceContext, ServiceUrl};
+use reqwest::{Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use std::sync::Arc;
+
+/// 已登录 CAS 的会话。CAS 登录成功后会在 login.xjtu.edu.cn 上留下 TGC cookie，
+/// 之后访问其他服务时 CAS 直接颁发票据，不需要再次提交密码，也不会再次触发二次验证。
+pub struct CasSession {
+    client: Client,
+    cookie_jar: Arc<CookieStoreMutex>,
+    webvpn: bool,
+    endpoints: Endpoints,
+}
+
+impl CasSession {
+    /// 只登录 CAS，不登录任何服务
+    pub async fn login(
+        username: &str,
+        password: &str,
+        options: &LoginOptions<'_>,
+    ) -> Result<Self, LoginError> {
+        let cookie_jar = Arc::new(CookieStoreMutex::default());
+        let client = build_client(cookie_jar.clone()).map_err(LoginError::RequestError)?;
+        let endpoints = options.endpoints.clone();
+        // 用选课系统探测能否直接访问校内服务
+        let webvpn = use_webvpn(&client, options.mode, &endpoints.course_url("/")).await;
+        if webvpn {
+            login_webvpn(&client, username, password, options).await?;
+        }
+        // 没有 service 参数时，成功后 CAS 显示自己的登录成功页，失败的情况由 cas_login 识别
+        cas_login(
+            &client,
+            &endpoints.route(&endpoints.cas_url(CAS_LOGIN_PATH), webvpn),
+            username,
+            password,
+            options,
+            webvpn,
+        )
+        .await?;
+        log::info!("CAS login succeeded");
+        Ok(Self {
+            client,
+            cookie_jar,
+            webvpn,
+            endpoints,
+        })
+    }
+
+    /// 复用某次服务登录留下的 CAS 会话，例如先登录选课系统，再用同一个会话登录 AI 平台
+    pub fn from_login(login: &LoginSuccess) -> Self {
+        Self {
+            client: login.client.clone(),
+            cookie_jar: login.cookie_jar.clone(),
+            webvpn: login.webvpn,
+            endpoints: login.endpoints.clone(),
+        }
+    }
+
+    /// 以 CAS 会话的身份访问服务。返回的客户端与本会话共用 cookie。
+    pub async fn login_service(
+        &self,
+        service: impl CasService,
+    ) -> Result<LoginSuccess, LoginError> {
+        Ok(self.authorize(service).await?.login)
+    }
+
+    /// 以 CAS 会话的身份换取任意服务的票据，见 [`login::service_ticket`]
+    pub async fn service_ticket(&self, service_url: &str) -> Result<ServiceTicket, LoginError> {
+        self.authorize(ServiceUrl::new(service_url))
+            .await?
+            .into_ticket()
+    }
+
+    /// 会话的客户端和 cookie
+    pub fn login_success(&self) -> LoginSuccess {
+        LoginSuccess {
+            client: self.client.clone(),
+            cookie_jar: self.cookie_jar.clone(),
+            webvpn: self.webvpn,
+            endpoints: self.endpoints.clone(),
+        }
+    }
+
+    async fn authorize(&self, service: impl CasService) -> Result<Authenticated, LoginError> {
+        log::info!("Logging in to service via CAS session: {}", service.name());
+        let ctx = ServiceContext {
+            client: &self.client,
+            webvpn: self.webvpn,
+            endpoints: &self.endpoints,
+        };
+        let login_url = service.login_url(&ctx).await?;
+        let resp = self
+            .client
+            .get(&login_url)
+            .send()
+            .await
+            .map_err(LoginError::RequestError)?;
+        // TGC 有效时 CAS 直接重定向回服务；重新显示登录页说明 CAS 会话已失效
+        if resp.status() == StatusCode::OK && is_cas_login_url(resp.url()) {
+            return Err(LoginError::SsoExpired);
+        }
+        let callback_url = expect_redirect(&resp)?;
+        let landing = login::land(&service, &ctx, &callback_url).await?;
+        Ok(Authenticated {
+            login: self.login_success(),
+            callback_url,
+            landing,
+        })
+    }
+}
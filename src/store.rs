@@ -0,0 +1,189 @@
+use crate::course::CourseSession;
+use crate::endpoints::Endpoints;
+use crate::login::{self, LoginError, LoginOptions, LoginSuccess, Service};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("Session file I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Session file format error: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("Cookie store error: {0}")]
+    Cookies(String),
+    #[error("HTTP client error: {0}")]
+    Client(#[from] reqwest::Error),
+}
+
+/// 选课系统 register.do 返回的会话信息
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CourseToken {
+    number: String,
+    name: String,
+    token: String,
+}
+
+/// 会话文件的内容
+#[derive(Serialize, Deserialize)]
+struct SavedSession {
+    /// cookie_store 的 JSON 序列化结果，包括会话 cookie（CAS 的 cookie 都没有过期时间）
+    cookies: String,
+    /// 是否经由 WebVPN 访问
+    webvpn: bool,
+    /// 选课系统的会话信息
+    course: Option<CourseToken>,
+    /// 登录时使用的服务地址，旧版本的会话文件中没有该字段
+    #[serde(default)]
+    endpoints: Endpoints,
+}
+
+/// 从会话文件中恢复的会话
+pub struct StoredSession {
+    /// 使用保存的 cookie 构造的客户端
+    pub login: LoginSuccess,
+    course: Option<CourseToken>,
+}
+
+impl StoredSession {
+    /// 恢复选课会话并验证其有效性；会话已失效或文件中没有选课信息时返回 `None`
+    pub async fn course_session(&self) -> Option<CourseSession> {
+        let saved = self.course.clone()?;
+        let session = CourseSession::from_saved(
+            self.login.client.clone(),
+            saved.number,
+            saved.name,
+            saved.token,
+            self.login.webvpn,
+            self.login.endpoints.clone(),
+        );
+        if session.is_alive().await {
+            Some(session)
+        } else {
+            log::info!("Stored course session has expired");
+            None
+        }
+    }
+}
+
+/// 将登录后的 cookie 和选课 token 保存到磁盘，下次运行时免去完整的 CAS 登录
+pub struct SessionStore {
+    path: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// 保存会话。文件中含有登录凭据，在 Unix 上权限会被设为 0600。
+    pub fn save(
+        &self,
+        login: &LoginSuccess,
+        course: Option<&CourseSession>,
+    ) -> Result<(), StoreError> {
+        let mut cookies = Vec::new();
+        {
+            let store = login
+                .cookie_jar
+                .lock()
+                .map_err(|e| StoreError::Cookies(e.to_string()))?;
+            cookie_store::serde::json::save_incl_expired_and_nonpersistent(&store, &mut cookies)
+                .map_err(|e| StoreError::Cookies(e.to_string()))?;
+        }
+        let saved = SavedSession {
+            cookies: String::from_utf8_lossy(&cookies).into_owned(),
+            webvpn: login.webvpn,
+            course: course.map(|c| CourseToken {
+                number: c.number.clone(),
+                name: c.name.clone(),
+                token: c.token().to_string(),
+            }),
+            endpoints: login.endpoints.clone(),
+        };
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&self.path)?;
+        // mode 只在创建文件时生效，已存在的文件需要单独收紧权限
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(serde_json::to_string(&saved)?.as_bytes())?;
+        log::info!("Session saved to {}", self.path.display());
+        Ok(())
+    }
+
+    /// 读取会话文件，文件不存在时返回 `None`。不检查会话是否有效。
+    pub fn load(&self) -> Result<Option<StoredSession>, StoreError> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let saved: SavedSession = serde_json::from_reader(BufReader::new(file))?;
+        let store = cookie_store::serde::json::load_all(saved.cookies.as_bytes())
+            .map_err(|e| StoreError::Cookies(e.to_string()))?;
+        let cookie_jar = Arc::new(CookieStoreMutex::new(store));
+        let client = login::build_client(cookie_jar.clone())?;
+        Ok(Some(StoredSession {
+            login: LoginSuccess {
+                client,
+                cookie_jar,
+                webvpn: saved.webvpn,
+                endpoints: saved.endpoints,
+            },
+            course: saved.course,
+        }))
+    }
+
+    /// 删除会话文件
+    pub fn clear(&self) -> Result<(), StoreError> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// 优先使用保存的选课会话；会话失效或不存在时重新登录，并保存新的会话
+    pub async fn course_session_or_login(
+        &self,
+        username: &str,
+        password: &str,
+        options: &LoginOptions<'_>,
+    ) -> Result<(LoginSuccess, CourseSession), LoginError> {
+        match self.load() {
+            Ok(Some(stored)) => {
+                if let Some(session) = stored.course_session().await {
+                    log::info!("Restored course session from {}", self.path.display());
+                    return Ok((stored.login, session));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Ignoring unreadable session file: {e}"),
+        }
+        let login =
+            login::login_with_options(Service::CourseSelection, username, password, options)
+                .await?;
+        let session = CourseSession::from_login(&login)
+            .await
+            .map_err(|e| LoginError::Other(format!("Failed to fetch course token: {e}")))?;
+        if let Err(e) = self.save(&login, Some(&session)) {
+            log::warn!("Failed to save session: {e}");
+        }
+        Ok((login, session))
+    }
+}
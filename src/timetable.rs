@@ -0,0 +1,607 @@
+use crate::course::TeachingClass;
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// 单双周
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Parity {
+    #[default]
+    All,
+    Odd,
+    Even,
+}
+
+impl Parity {
+    fn accepts(self, week: u32) -> bool {
+        match self {
+            Parity::All => true,
+            Parity::Odd => week % 2 == 1,
+            Parity::Even => week.is_multiple_of(2),
+        }
+    }
+}
+
+/// 周次集合，支持第 1 到 63 周
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Weeks(u64);
+
+impl Weeks {
+    pub const MAX: u32 = 63;
+
+    /// `first` 到 `last` 周中符合单双周要求的周次，超出范围的周次被忽略
+    pub fn range(first: u32, last: u32, parity: Parity) -> Self {
+        let mut weeks = Weeks::default();
+        for week in first.max(1)..=last.min(Self::MAX) {
+            if parity.accepts(week) {
+                weeks.insert(week);
+            }
+        }
+        weeks
+    }
+
+    pub fn insert(&mut self, week: u32) {
+        if (1..=Self::MAX).contains(&week) {
+            self.0 |= 1 << week;
+        }
+    }
+
+    pub fn contains(&self, week: u32) -> bool {
+        week <= Self::MAX && self.0 & (1 << week) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub fn union(self, other: Weeks) -> Weeks {
+        Weeks(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: Weeks) -> Weeks {
+        Weeks(self.0 & other.0)
+    }
+
+    /// 从小到大的周次
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (1..=Self::MAX).filter(|week| self.contains(*week))
+    }
+}
+
+/// e.g. `1-16周`、`1-15周(单)`、`1-4,6-8周`
+impl Display for Weeks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let weeks: Vec<u32> = self.iter().collect();
+        if weeks.len() > 2 && weeks.windows(2).all(|w| w[1] - w[0] == 2) {
+            let parity = if weeks[0] % 2 == 1 { "单" } else { "双" };
+            return write!(f, "{}-{}周({parity})", weeks[0], weeks[weeks.len() - 1]);
+        }
+        let mut ranges: Vec<String> = Vec::new();
+        let mut start = 0;
+        for i in 0..weeks.len() {
+            if i + 1 == weeks.len() || weeks[i + 1] != weeks[i] + 1 {
+                ranges.push(if start == i {
+                    weeks[i].to_string()
+                } else {
+                    format!("{}-{}", weeks[start], weeks[i])
+                });
+                start = i + 1;
+            }
+        }
+        write!(f, "{}周", ranges.join(","))
+    }
+}
+
+/// 一次上课安排：哪些周的星期几第几节，在哪里上课
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Meeting {
+    pub weeks: Weeks,
+    pub weekday: Weekday,
+    /// 开始节次
+    pub first_period: u8,
+    /// 结束节次（含）
+    pub last_period: u8,
+    /// 校区 e.g. 兴庆校区
+    pub campus: Option<String>,
+    /// 教学楼 e.g. 主楼A
+    pub building: Option<String>,
+    /// 教室 e.g. 101
+    pub room: Option<String>,
+}
+
+impl Meeting {
+    /// 教学楼和教室，e.g. `主楼A-101`
+    pub fn location(&self) -> Option<String> {
+        match (&self.building, &self.room) {
+            (Some(building), Some(room)) => Some(format!("{building}-{room}")),
+            (Some(place), None) | (None, Some(place)) => Some(place.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// 星期几的中文名称
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "星期一",
+        Weekday::Tue => "星期二",
+        Weekday::Wed => "星期三",
+        Weekday::Thu => "星期四",
+        Weekday::Fri => "星期五",
+        Weekday::Sat => "星期六",
+        Weekday::Sun => "星期日",
+    }
+}
+
+/// e.g. `1-16周 星期一 第1-2节 兴庆校区 主楼A-101`
+impl Display for Meeting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ", self.weeks, weekday_name(self.weekday))?;
+        if self.first_period == self.last_period {
+            write!(f, "第{}节", self.first_period)?;
+        } else {
+            write!(f, "第{}-{}节", self.first_period, self.last_period)?;
+        }
+        if let Some(campus) = &self.campus {
+            write!(f, " {campus}")?;
+        }
+        if let Some(location) = self.location() {
+            write!(f, " {location}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 解析后的上课时间地点
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub meetings: Vec<Meeting>,
+    /// 无法解析的片段，原样保留
+    pub unparsed: Vec<String>,
+}
+
+/// 解析教学班的 `teaching_place`，e.g. `1-16周 星期一 第1-2节 主楼A-101`。
+///
+/// 多次上课安排之间以逗号或分号分隔；周次、星期和节次的顺序不限，之间可以没有空格；
+/// 周次支持 `1-4,6-8周`、`1-15周(单)`、`2-16双周` 等写法；其余部分作为地点，
+/// 其中的 `XX校区` 或 `创新港` 视为校区。无法解析的片段保存在 [`Schedule::unparsed`] 中。
+pub fn parse_teaching_place(text: &str) -> Schedule {
+    let mut schedule = Schedule::default();
+    for segment in segments(text) {
+        match parse_meeting(&segment) {
+            Some(meeting) => schedule.meetings.push(meeting),
+            None => schedule.unparsed.push(segment),
+        }
+    }
+    schedule
+}
+
+/// 按逗号和分号切分，但周次和节次列表中的逗号（如 `1-4,6-8周`）不算
+fn segments(text: &str) -> Vec<String> {
+    let pieces: Vec<&str> = text
+        .split([',', '，', ';', '；', '\n'])
+        .map(str::trim)
+        .filter(|piece| !piece.is_empty())
+        .collect();
+    let mut segments: Vec<String> = Vec::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        match segments.last_mut() {
+            Some(last) if continues_list(last, &pieces[i..]) => {
+                last.push(',');
+                last.push_str(piece);
+            }
+            _ => segments.push(piece.to_string()),
+        }
+    }
+    segments
+}
+
+/// `last` 以没有结束的数字列表结尾，且后面的片段以 `周` 或 `节` 结束这个列表，
+/// 而 `last` 中还没有同一种列表。后一个条件排除了纯数字的教室号，
+/// e.g. `1-16周 星期二 第5-6节 3-301,1-16周 星期四 ...` 中的 `3-301`。
+fn continues_list(last: &str, rest: &[&str]) -> bool {
+    if !ends_with_open_list(last) {
+        return false;
+    }
+    // 跳过同样没有结束的片段，e.g. `1,3,5周` 中的 `3`
+    let Some(unit) = rest
+        .iter()
+        .find(|piece| !is_open_list(piece))
+        .and_then(|piece| list_unit(piece))
+    else {
+        return false;
+    };
+    let (tokens, _) = tokens(last);
+    !tokens.iter().any(|token| match token {
+        Token::Numbered(Numbered::Weeks(_)) => unit == '周',
+        Token::Numbered(Numbered::Periods(..)) => unit == '节',
+        Token::Weekday(_) => false,
+    })
+}
+
+/// 最后一个词是没有以 `周` 或 `节` 结尾的数字列表，e.g. `1-4`、`1-5单` 或 `第1`
+fn ends_with_open_list(text: &str) -> bool {
+    let word = text.split_whitespace().last().unwrap_or_default();
+    let word = word.strip_prefix('第').unwrap_or(word);
+    word.starts_with(|c: char| c.is_ascii_digit())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_digit() || "-,单双".contains(c))
+}
+
+/// 整个片段只是一个没有结束的数字列表
+fn is_open_list(piece: &str) -> bool {
+    !piece.contains(char::is_whitespace) && ends_with_open_list(piece)
+}
+
+/// 片段开头的数字列表以 `周` 或 `节` 结束时返回这个字
+fn list_unit(piece: &str) -> Option<char> {
+    let word = piece.strip_prefix('第').unwrap_or(piece);
+    if !word.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    word.chars()
+        .find(|c| !(c.is_ascii_digit() || "-~,单双".contains(*c)))
+        .filter(|c| matches!(c, '周' | '节'))
+}
+
+enum Token {
+    Weekday(Weekday),
+    Numbered(Numbered),
+}
+
+/// 从开头依次读取星期、周次和节次，返回读到的内容和剩下的地点部分
+fn tokens(segment: &str) -> (Vec<Token>, &str) {
+    let mut rest = segment;
+    let mut tokens = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some((day, r)) = parse_weekday(rest) {
+            tokens.push(Token::Weekday(day));
+            rest = r;
+        } else if let Some((numbered, r)) = parse_numbered(rest) {
+            tokens.push(Token::Numbered(numbered));
+            rest = r;
+        } else {
+            return (tokens, rest);
+        }
+    }
+}
+
+/// 星期或节次出现两次的片段无法确定是哪一次上课，不予解析
+fn parse_meeting(segment: &str) -> Option<Meeting> {
+    let (tokens, rest) = tokens(segment);
+    let mut weeks: Option<Weeks> = None;
+    let mut weekday = None;
+    let mut periods = None;
+    for token in tokens {
+        match token {
+            Token::Weekday(day) => {
+                if weekday.replace(day).is_some() {
+                    return None;
+                }
+            }
+            Token::Numbered(Numbered::Weeks(w)) => {
+                weeks = Some(weeks.unwrap_or_default().union(w));
+            }
+            Token::Numbered(Numbered::Periods(first, last)) => {
+                if periods.replace((first, last)).is_some() {
+                    return None;
+                }
+            }
+        }
+    }
+    let weeks = weeks.filter(|w| !w.is_empty())?;
+    let (first_period, last_period) = periods?;
+    let (campus, building, room) = parse_location(rest);
+    Some(Meeting {
+        weeks,
+        weekday: weekday?,
+        first_period,
+        last_period,
+        campus,
+        building,
+        room,
+    })
+}
+
+/// `星期一`、`周一`、`星期日`、`星期天`、`星期7` 等
+fn parse_weekday(text: &str) -> Option<(Weekday, &str)> {
+    let rest = text
+        .strip_prefix("星期")
+        .or_else(|| text.strip_prefix("礼拜"))
+        .or_else(|| text.strip_prefix('周'))?;
+    let mut chars = rest.chars();
+    let day = match chars.next()? {
+        '一' | '1' => Weekday::Mon,
+        '二' | '2' => Weekday::Tue,
+        '三' | '3' => Weekday::Wed,
+        '四' | '4' => Weekday::Thu,
+        '五' | '5' => Weekday::Fri,
+        '六' | '6' => Weekday::Sat,
+        '日' | '天' | '七' | '7' => Weekday::Sun,
+        _ => return None,
+    };
+    Some((day, chars.as_str()))
+}
+
+enum Numbered {
+    Weeks(Weeks),
+    Periods(u8, u8),
+}
+
+/// 以 `周` 或 `节` 结尾的数字列表，e.g. `1-4,6-8周`、`1-15单周`、`1-15周(单)`、`第3-4节`
+fn parse_numbered(text: &str) -> Option<(Numbered, &str)> {
+    let mut rest = text.strip_prefix('第').unwrap_or(text);
+    let mut items: Vec<(u32, u32, Parity)> = Vec::new();
+    loop {
+        let (first, r) = parse_number(rest)?;
+        rest = r;
+        let mut last = first;
+        if let Some(r) = rest
+            .strip_prefix('-')
+            .or_else(|| rest.strip_prefix('~'))
+            .or_else(|| rest.strip_prefix('—'))
+            .or_else(|| rest.strip_prefix('－'))
+        {
+            (last, rest) = parse_number(r)?;
+        }
+        let (parity, r) = parse_parity(rest);
+        rest = r;
+        items.push((first, last, parity));
+        match rest.strip_prefix([',', '、']) {
+            Some(r) => rest = r,
+            None => break,
+        }
+    }
+    if let Some(r) = rest.strip_prefix('周') {
+        let (parity, r) = parse_parity(r);
+        let weeks = items
+            .into_iter()
+            .fold(Weeks::default(), |weeks, (first, last, p)| {
+                let p = if p == Parity::All { parity } else { p };
+                weeks.union(Weeks::range(first, last, p))
+            });
+        Some((Numbered::Weeks(weeks), r))
+    } else if let Some(r) = rest.strip_prefix('节') {
+        let first = items.iter().map(|i| i.0.min(i.1)).min()?;
+        let last = items.iter().map(|i| i.0.max(i.1)).max()?;
+        let first = u8::try_from(first).ok().filter(|p| *p > 0)?;
+        Some((Numbered::Periods(first, u8::try_from(last).ok()?), r))
+    } else {
+        None
+    }
+}
+
+fn parse_number(text: &str) -> Option<(u32, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    Some((text[..end].parse().ok()?, &text[end..]))
+}
+
+/// `单`、`双`、`(单)`、`（双）`、`单周`（仅在后面还有 `周` 时消费 `单`）
+fn parse_parity(text: &str) -> (Parity, &str) {
+    for (open, close) in [('(', ')'), ('（', '）'), ('[', ']')] {
+        if let Some(inner) = text.strip_prefix(open)
+            && let Some((word, rest)) = inner.split_once(close)
+        {
+            match word.trim_end_matches('周') {
+                "单" => return (Parity::Odd, rest),
+                "双" => return (Parity::Even, rest),
+                _ => {}
+            }
+        }
+    }
+    if let Some(rest) = text.strip_prefix('单') {
+        return (Parity::Odd, rest);
+    }
+    if let Some(rest) = text.strip_prefix('双') {
+        return (Parity::Even, rest);
+    }
+    (Parity::All, text)
+}
+
+type Location = (Option<String>, Option<String>, Option<String>);
+
+/// 拆分校区、教学楼和教室
+fn parse_location(text: &str) -> Location {
+    let mut campus = None;
+    let mut text = text.to_string();
+    // 括号中的校区可能紧跟在地点之后，e.g. 主楼A-101(兴庆校区)
+    for (open, close) in [('(', ')'), ('（', '）'), ('[', ']'), ('【', '】')] {
+        if let Some(start) = text.find(open)
+            && let Some(len) = text[start..].find(close)
+        {
+            let inner = &text[start + open.len_utf8()..start + len];
+            if inner.ends_with("校区") || inner == "创新港" {
+                campus = Some(inner.to_string());
+                text.replace_range(start..start + len + close.len_utf8(), " ");
+                break;
+            }
+        }
+    }
+    let mut words: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let inner = word.trim_matches(|c| "()（）[]【】".contains(c));
+        if campus.is_none() && (inner.ends_with("校区") || inner == "创新港") {
+            campus = Some(inner.to_string());
+        } else if campus.is_none()
+            && let Some(index) = word.find("校区")
+        {
+            // 校区和地点连在一起，e.g. 兴庆校区主楼A-101
+            let (name, place) = word.split_at(index + "校区".len());
+            campus = Some(name.to_string());
+            words.push(place.to_string());
+        } else {
+            words.push(word.to_string());
+        }
+    }
+    let place = words.join(" ");
+    if place.is_empty() {
+        return (campus, None, None);
+    }
+    if let Some((building, room)) = place.rsplit_once('-')
+        && !building.is_empty()
+        && room.chars().any(|c| c.is_ascii_digit())
+        && room.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return (
+            campus,
+            Some(building.trim().to_string()),
+            Some(room.to_string()),
+        );
+    }
+    // 教室号直接接在楼名之后，e.g. 西二楼203
+    let digits = place.len() - place.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && digits < place.len() {
+        let (building, room) = place.split_at(place.len() - digits);
+        return (
+            campus,
+            Some(building.trim().to_string()),
+            Some(room.to_string()),
+        );
+    }
+    (campus, Some(place), None)
+}
+
+/// 两次上课安排重叠的部分
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Overlap {
+    pub weeks: Weeks,
+    pub weekday: Weekday,
+    pub first_period: u8,
+    pub last_period: u8,
+}
+
+/// e.g. `1-8周 星期一 第2节`
+impl Display for Overlap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ", self.weeks, weekday_name(self.weekday))?;
+        if self.first_period == self.last_period {
+            write!(f, "第{}节", self.first_period)
+        } else {
+            write!(f, "第{}-{}节", self.first_period, self.last_period)
+        }
+    }
+}
+
+impl Meeting {
+    /// 与另一次上课安排在同一星期几的相同周次、相同节次上课时，返回重叠的部分
+    pub fn overlap(&self, other: &Meeting) -> Option<Overlap> {
+        if self.weekday != other.weekday {
+            return None;
+        }
+        let weeks = self.weeks.intersection(other.weeks);
+        let first_period = self.first_period.max(other.first_period);
+        let last_period = self.last_period.min(other.last_period);
+        (!weeks.is_empty() && first_period <= last_period).then_some(Overlap {
+            weeks,
+            weekday: self.weekday,
+            first_period,
+            last_period,
+        })
+    }
+}
+
+impl Schedule {
+    /// 与另一个上课安排的所有重叠部分
+    pub fn overlaps(&self, other: &Schedule) -> Vec<Overlap> {
+        self.meetings
+            .iter()
+            .flat_map(|a| other.meetings.iter().filter_map(|b| a.overlap(b)))
+            .collect()
+    }
+
+    pub fn conflicts_with(&self, other: &Schedule) -> bool {
+        self.meetings
+            .iter()
+            .any(|a| other.meetings.iter().any(|b| a.overlap(b).is_some()))
+    }
+}
+
+/// 两个教学班之间的冲突
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Conflict {
+    pub first: String,
+    pub second: String,
+    pub overlaps: Vec<Overlap>,
+}
+
+/// 一组（假设选上的）教学班的课表，用于在本地检查它们之间的冲突
+#[derive(Debug, Clone, Default)]
+pub struct Timetable {
+    entries: Vec<(String, Schedule)>,
+}
+
+impl Timetable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加入一个教学班，`class_id` 用于在冲突报告中标识它
+    pub fn add(&mut self, class_id: impl Into<String>, schedule: Schedule) {
+        self.entries.push((class_id.into(), schedule));
+    }
+
+    /// 加入一个教学班，上课安排从 [`TeachingClass::teaching_place`] 解析
+    pub fn add_class(&mut self, class: &TeachingClass) {
+        self.add(class.teaching_class_id.clone(), class.schedule());
+    }
+
+    /// 含有无法解析的上课安排的教学班，这些部分不参与冲突检查
+    pub fn unparsed(&self) -> Vec<(&str, &[String])> {
+        self.entries
+            .iter()
+            .filter(|(_, schedule)| !schedule.unparsed.is_empty())
+            .map(|(id, schedule)| (id.as_str(), schedule.unparsed.as_slice()))
+            .collect()
+    }
+
+    /// 所有两两冲突的教学班，按加入的顺序排列
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for (i, (first, a)) in self.entries.iter().enumerate() {
+            for (second, b) in &self.entries[i + 1..] {
+                let overlaps = a.overlaps(b);
+                if !overlaps.is_empty() {
+                    conflicts.push(Conflict {
+                        first: first.clone(),
+                        second: second.clone(),
+                        overlaps,
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// 加入 `schedule` 后会产生的冲突
+    pub fn conflicts_with(&self, class_id: &str, schedule: &Schedule) -> Vec<Conflict> {
+        self.entries
+            .iter()
+            .filter_map(|(id, existing)| {
+                let overlaps = existing.overlaps(schedule);
+                (!overlaps.is_empty()).then(|| Conflict {
+                    first: id.clone(),
+                    second: class_id.to_string(),
+                    overlaps,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'a> FromIterator<&'a TeachingClass> for Timetable {
+    fn from_iter<I: IntoIterator<Item = &'a TeachingClass>>(iter: I) -> Self {
+        let mut timetable = Timetable::new();
+        for class in iter {
+            timetable.add_class(class);
+        }
+        timetable
+    }
+}
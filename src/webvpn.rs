@@ -0,0 +1,90 @@
+use aes::Aes128;
+use cfb_mode::cipher::{AsyncStreamCipher, KeyIvInit};
+use cfb_mode::{Decryptor, Encryptor};
+use reqwest::{Client, Url};
+use std::time::Duration;
+
+/// WebVPN 网关地址
+pub static WEBVPN_ORIGIN: &str = "https://webvpn.xjtu.edu.cn";
+
+/// 网关登录入口的路径，见 [`crate::endpoints::Endpoints::webvpn_url`]
+pub static WEBVPN_LOGIN_PATH: &str = "/login?cas_login=true";
+
+/// 网关加密主机名所用的 AES key 和 IV（WRD WebVPN 的固定值）
+const WEBVPN_KEY: &[u8; 16] = b"wrdvpnisthebest!";
+const WEBVPN_IV: &[u8; 16] = b"wrdvpnisthebest!";
+
+/// 探测校内服务是否可直接访问的超时时间
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 访问校内服务的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessMode {
+    /// 直接访问，适用于校园网内
+    Direct,
+    /// 经由 WebVPN 网关访问，适用于校外
+    WebVpn,
+    /// 先尝试直接访问，不可达时改用 WebVPN
+    #[default]
+    Auto,
+}
+
+/// 加密主机名，得到网关路径中的主机段：IV 的十六进制 + AES-128-CFB 密文的十六进制。
+pub fn encrypt_host(host: &str) -> String {
+    let mut buf = host.as_bytes().to_vec();
+    Encryptor::<Aes128>::new(WEBVPN_KEY.into(), WEBVPN_IV.into()).encrypt(&mut buf);
+    let mut encoded = String::with_capacity((WEBVPN_IV.len() + buf.len()) * 2);
+    for byte in WEBVPN_IV.iter().chain(buf.iter()) {
+        encoded.push_str(&format!("{byte:02x}"));
+    }
+    encoded
+}
+
+/// [`encrypt_host`] 的逆运算，格式不对时返回 `None`
+pub fn decrypt_host(encoded: &str) -> Option<String> {
+    let bytes = (0..encoded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(encoded.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let (iv, cipher) = bytes.split_at_checked(WEBVPN_IV.len())?;
+    let mut buf = cipher.to_vec();
+    Decryptor::<Aes128>::new(WEBVPN_KEY.into(), iv.into()).decrypt(&mut buf);
+    String::from_utf8(buf).ok()
+}
+
+/// 将校内服务的 URL 改写为网关 `origin` 下的形式，例如
+/// `https://xkfw.xjtu.edu.cn/a?b=1` -> `https://webvpn.xjtu.edu.cn/https/77726476.../a?b=1`。
+/// 无法解析的 URL 或已经指向网关的 URL 原样返回。按访问方式选择 URL 见
+/// [`crate::endpoints::Endpoints::route`]。
+pub fn rewrite_url_with_origin(origin: &str, url: &str) -> String {
+    let Ok(parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    let Some(host) = parsed.host_str() else {
+        return url.to_string();
+    };
+    if parsed.origin().ascii_serialization() == origin {
+        return url.to_string();
+    }
+    let scheme = match parsed.port() {
+        Some(port) => format!("{}-{}", parsed.scheme(), port),
+        None => parsed.scheme().to_string(),
+    };
+    let mut rewritten = format!("{origin}/{scheme}/{}{}", encrypt_host(host), parsed.path());
+    if let Some(query) = parsed.query() {
+        rewritten.push('?');
+        rewritten.push_str(query);
+    }
+    rewritten
+}
+
+/// 探测能否直接访问给定的校内地址。只要能建立连接并收到任何响应即视为可达。
+pub async fn direct_reachable(client: &Client, url: &str) -> bool {
+    match client.head(url).timeout(PROBE_TIMEOUT).send().await {
+        Ok(_) => true,
+        Err(e) => {
+            log::info!("{url} is not directly reachable: {e}");
+            false
+        }
+    }
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use common::managed;
+use futures_util::StreamExt;
+use xjtu_login::catalog::{Catalog, CatalogConfig};
+use xjtu_login::course::CourseType;
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+
+/// `count` 门课程，每门两个教学班
+fn scenario(count: usize) -> Scenario {
+    let classes = (0..count)
+        .flat_map(|i| {
+            let number = format!("ECON{:04}", i);
+            let name = if i % 2 == 0 {
+                "国际结算"
+            } else {
+                "货币银行学"
+            };
+            [
+                MockClass::new(format!("TC-{i}-1"), &number, name, 30),
+                MockClass::new(format!("TC-{i}-2"), &number, name, 30),
+            ]
+        })
+        .collect();
+    Scenario {
+        classes,
+        ..Default::default()
+    }
+}
+
+fn numbers(courses: &[xjtu_login::course::CourseInfo]) -> Vec<String> {
+    courses.iter().map(|c| c.course_number.clone()).collect()
+}
+
+#[tokio::test]
+async fn walks_all_pages_in_order() {
+    let server = MockServer::start(scenario(23)).await.unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+
+    let page = session
+        .list_course_page(batch, CourseType::TJKC, 4, 5, "")
+        .await
+        .unwrap();
+    assert_eq!(page.total, Some(23));
+    assert_eq!(page.courses.len(), 3);
+
+    let expected: Vec<String> = (0..23).map(|i| format!("ECON{:04}", i)).collect();
+    for (page_size, concurrency) in [(5, 3), (23, 1), (100, 4), (1, 8)] {
+        let courses = Catalog::new(&session, batch, CourseType::TJKC, "")
+            .with_config(CatalogConfig {
+                page_size,
+                concurrency,
+            })
+            .fetch_all()
+            .await
+            .unwrap();
+        assert_eq!(numbers(&courses), expected, "page size {page_size}");
+        assert!(courses.iter().all(|c| c.tc_list.len() == 2));
+    }
+}
+
+#[tokio::test]
+async fn applies_query_and_course_type() {
+    let server = MockServer::start(scenario(12)).await.unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let config = CatalogConfig {
+        page_size: 4,
+        concurrency: 2,
+    };
+
+    let courses = Catalog::new(&session, batch, CourseType::TJKC, "国际结算")
+        .with_config(config.clone())
+        .fetch_all()
+        .await
+        .unwrap();
+    assert_eq!(courses.len(), 6);
+    assert!(courses.iter().all(|c| c.course_name == "国际结算"));
+
+    let courses = Catalog::new(&session, batch, CourseType::XGXK, "")
+        .with_config(config)
+        .fetch_all()
+        .await
+        .unwrap();
+    assert!(courses.is_empty());
+}
+
+#[tokio::test]
+async fn streams_across_expired_session() {
+    let server = MockServer::start(scenario(9)).await.unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let catalog = Catalog::new(&session, batch, CourseType::TJKC, "").with_config(CatalogConfig {
+        page_size: 3,
+        concurrency: 1,
+    });
+
+    let mut stream = Box::pin(catalog.stream());
+    let mut courses = Vec::new();
+    while let Some(course) = stream.next().await {
+        courses.push(course.unwrap());
+        if courses.len() == 3 {
+            // 第一页取完后会话过期，后续页面自动重新登录
+            server.expire_course_sessions();
+        }
+    }
+    assert_eq!(courses.len(), 9);
+    assert_eq!(numbers(&courses)[8], "ECON0008");
+}
+
+#[tokio::test]
+async fn follows_server_page_size_cap() {
+    let server = MockServer::start(Scenario {
+        max_page_size: Some(4),
+        ..scenario(11)
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let courses = Catalog::new(&session, batch, CourseType::TJKC, "")
+        .with_config(CatalogConfig {
+            page_size: 10,
+            concurrency: 3,
+        })
+        .fetch_all()
+        .await
+        .unwrap();
+    let expected: Vec<String> = (0..11).map(|i| format!("ECON{:04}", i)).collect();
+    assert_eq!(numbers(&courses), expected);
+}
+
+#[tokio::test]
+async fn works_without_total_count() {
+    let server = MockServer::start(Scenario {
+        omit_total_count: true,
+        ..scenario(11)
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let courses = session
+        .list_course(batch, CourseType::TJKC, 1, "")
+        .await
+        .unwrap();
+    assert_eq!(courses.len(), 1);
+
+    let courses = Catalog::new(&session, batch, CourseType::TJKC, "")
+        .with_config(CatalogConfig {
+            page_size: 3,
+            concurrency: 2,
+        })
+        .fetch_all()
+        .await
+        .unwrap();
+    assert_eq!(courses.len(), 11);
+    assert_eq!(numbers(&courses)[10], "ECON0010");
+}
@@ -0,0 +1,115 @@
+//! 各集成测试共用的数据构造和登录函数
+#![allow(dead_code)]
+
+use xjtu_login::course::{CourseInfo, CourseSession, TeachingClass};
+use xjtu_login::login::{self, LoginOptions, LoginSuccess, Service};
+use xjtu_login::managed::{Credentials, ManagedCourseSession};
+use xjtu_login::mock::MockServer;
+
+/// 服务器返回的教学班数据，默认由张老师任教、未满、未选
+pub struct ClassData(serde_json::Value);
+
+pub fn class(id: &str, place: &str) -> ClassData {
+    ClassData(serde_json::json!({
+        "courseNumber": "ECON2001",
+        "teachingClassID": id,
+        "teacherName": "张老师",
+        "teachingPlace": place,
+        "classCapacity": "30",
+        "numberOfSelected": "0",
+        "limitGender": "0",
+        "isChoose": "0",
+        "isFull": "0",
+        "isConflict": "0"
+    }))
+}
+
+impl ClassData {
+    pub fn teacher(mut self, name: &str) -> Self {
+        self.0["teacherName"] = name.into();
+        self
+    }
+
+    pub fn full(mut self) -> Self {
+        self.0["numberOfSelected"] = "30".into();
+        self.0["isFull"] = "1".into();
+        self
+    }
+
+    pub fn chosen(mut self) -> Self {
+        self.0["isChoose"] = "1".into();
+        self
+    }
+
+    pub fn build(self) -> TeachingClass {
+        serde_json::from_value(self.0).unwrap()
+    }
+}
+
+/// 课程号和课程名称都为 `name` 的课程
+pub fn course(name: &str, classes: Vec<ClassData>) -> CourseInfo {
+    let selected = classes.iter().any(|c| c.0["isChoose"] == "1");
+    let classes: Vec<serde_json::Value> = classes.into_iter().map(|c| c.0).collect();
+    serde_json::from_value(serde_json::json!({
+        "departmentName": "经济与金融学院",
+        "courseNatureName": "必修",
+        "selected": selected,
+        "tcList": classes,
+        "courseNumber": name,
+        "courseName": name,
+        "type": "01",
+        "typeName": "专业必修课程",
+        "hours": "32",
+        "credit": "2",
+        "majorFlag": "主修"
+    }))
+    .unwrap()
+}
+
+/// 以模拟服务器的默认账号登录
+pub async fn managed(server: &MockServer) -> ManagedCourseSession {
+    let credentials = Credentials {
+        endpoints: server.endpoints(),
+        ..Credentials::new("2200000000", "password")
+    };
+    ManagedCourseSession::login(credentials).await.unwrap()
+}
+
+/// 指向模拟服务器的登录选项
+pub fn options(server: &MockServer) -> LoginOptions<'static> {
+    LoginOptions {
+        endpoints: server.endpoints(),
+        ..Default::default()
+    }
+}
+
+/// 以模拟服务器当前场景中的账号登录选课系统
+pub async fn login_course(server: &MockServer, options: &LoginOptions<'_>) -> LoginSuccess {
+    let scenario = server.scenario();
+    login::login_with_options(
+        Service::CourseSelection,
+        &scenario.username,
+        &scenario.password,
+        options,
+    )
+    .await
+    .expect("login should succeed")
+}
+
+/// 登录选课系统并取得选课会话
+pub async fn course_session_with(
+    username: &str,
+    password: &str,
+    options: &LoginOptions<'_>,
+) -> CourseSession {
+    let login = login::login_with_options(Service::CourseSelection, username, password, options)
+        .await
+        .unwrap();
+    CourseSession::from_login(&login).await.unwrap()
+}
+
+/// 以模拟服务器当前场景中的账号取得选课会话
+pub async fn course_session(server: &MockServer) -> CourseSession {
+    let scenario = server.scenario();
+    course_session_with(&scenario.username, &scenario.password, &options(server)).await
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::course_session_with;
+use xjtu_login::course::CourseType;
+use xjtu_login::endpoints::Endpoints;
+use xjtu_login::fixture::{Exchange, Fixture, Host, Recorder, Replayer};
+use xjtu_login::login::LoginOptions;
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+use xjtu_login::webvpn::AccessMode;
+
+const USERNAME: &str = "2212345678";
+const PASSWORD: &str = "s3cret-pass";
+
+/// 登录选课系统并完成一次选课，返回学号、批次名称和选课后的已选人数
+async fn run_flow(endpoints: Endpoints) -> (String, String, u32) {
+    let options = LoginOptions {
+        mode: AccessMode::Direct,
+        endpoints,
+        ..Default::default()
+    };
+    let session = course_session_with(USERNAME, PASSWORD, &options).await;
+    let batches = session.get_batch_list().await.unwrap();
+    let courses = session
+        .list_course(&batches[0], CourseType::TJKC, 0, "")
+        .await
+        .unwrap();
+    let class_id = courses[0].tc_list[0].teaching_class_id.clone();
+    session
+        .add_volunteer(&batches[0], &class_id, CourseType::TJKC)
+        .await
+        .unwrap();
+    let capacity = session.get_capacity(&class_id).await.unwrap();
+    (
+        session.number.clone(),
+        batches[0].name.clone(),
+        capacity.number_of_selected,
+    )
+}
+
+#[tokio::test]
+async fn record_then_replay() {
+    let mock = MockServer::start(Scenario {
+        username: USERNAME.to_string(),
+        password: PASSWORD.to_string(),
+        classes: vec![MockClass::new("TC-1", "ECON2001", "国际结算", 30)],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let recorder = Recorder::start(
+        mock.endpoints(),
+        vec![USERNAME.to_string(), PASSWORD.to_string()],
+    )
+    .await
+    .unwrap();
+    let (number, batch, selected) = run_flow(recorder.endpoints()).await;
+    assert_eq!(number, USERNAME);
+    assert_eq!(selected, 1);
+
+    let path = std::env::temp_dir().join(format!("xjtu-login-fixture-{}.json", std::process::id()));
+    recorder.save(&path).unwrap();
+    drop(recorder);
+    drop(mock);
+    let fixture = Fixture::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let text = serde_json::to_string(&fixture).unwrap();
+    for secret in [
+        USERNAME,
+        PASSWORD,
+        "token-",
+        "ST-",
+        "TGT-",
+        "WEU-",
+        "127.0.0.1",
+    ] {
+        assert!(!text.contains(secret), "fixture leaks {secret}");
+    }
+    // 模拟服务器的 CAS、选课系统和 AI 平台在同一个地址上，统一替换为第一个占位符
+    assert!(text.contains("{{cas}}"));
+
+    let replayer = Replayer::start(fixture).await.unwrap();
+    let (number, replayed_batch, replayed_selected) = run_flow(replayer.endpoints()).await;
+    assert_eq!(number, "REDACTED");
+    assert_eq!(replayed_batch, batch);
+    assert_eq!(replayed_selected, 1);
+    assert_eq!(replayer.remaining(), 0);
+}
+
+fn exchange(host: Host, body: &str) -> Exchange {
+    Exchange {
+        host,
+        method: "GET".to_string(),
+        uri: "/status".to_string(),
+        request_body: String::new(),
+        status: 200,
+        headers: Vec::new(),
+        body: body.to_string(),
+        binary: false,
+    }
+}
+
+#[tokio::test]
+async fn replay_matches_host() {
+    let replayer = Replayer::start(Fixture {
+        exchanges: vec![exchange(Host::Course, "course"), exchange(Host::Cas, "cas")],
+    })
+    .await
+    .unwrap();
+    let endpoints = replayer.endpoints();
+    let get = |origin: String| async move {
+        let response = reqwest::get(format!("{origin}/status")).await.unwrap();
+        (response.status().as_u16(), response.text().await.unwrap())
+    };
+    // 同一路径在不同服务上的记录互不混用
+    assert_eq!(get(endpoints.cas.clone()).await, (200, "cas".to_string()));
+    assert_eq!(get(endpoints.ai.clone()).await.0, 404);
+    assert_eq!(replayer.remaining(), 1);
+    assert_eq!(
+        get(endpoints.course.clone()).await,
+        (200, "course".to_string())
+    );
+    assert_eq!(replayer.remaining(), 0);
+}
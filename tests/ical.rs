@@ -0,0 +1,117 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::{class, course};
+use xjtu_login::ical::{Calendar, PeriodTable};
+
+fn calendar() -> Calendar {
+    // 2025-09-03 是星期三，第一周从 2025-09-01 开始
+    Calendar::new(
+        NaiveDate::from_ymd_opt(2025, 9, 3).unwrap(),
+        PeriodTable::xjtu(false),
+    )
+}
+
+/// 展开折行后的内容行
+fn unfold(ics: &str) -> Vec<String> {
+    ics.replace("\r\n ", "")
+        .split("\r\n")
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn exports_selected_classes() {
+    let courses = [course(
+        "国际结算",
+        vec![
+            class("TC-1", "1-16周 星期一 第1-2节 兴庆校区 主楼A-101").chosen(),
+            class("TC-2", "1-16周 星期二 第1-2节 主楼A-102"),
+        ],
+    )];
+    let mut calendar = calendar();
+    calendar.add_selected(&courses);
+    let ics = calendar.to_ics();
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    let lines = unfold(&ics);
+    let has = |line: &str| lines.iter().any(|l| l == line);
+    assert_eq!(lines.iter().filter(|l| *l == "BEGIN:VEVENT").count(), 1);
+    assert!(has("UID:TC-1-0@xjtu-login"));
+    assert!(has("DTSTART;TZID=Asia/Shanghai:20250901T080000"));
+    assert!(has("DTEND;TZID=Asia/Shanghai:20250901T095000"));
+    assert!(has("RRULE:FREQ=WEEKLY;COUNT=16"));
+    assert!(has("SUMMARY:国际结算"));
+    assert!(has("LOCATION:兴庆校区 主楼A-101"));
+    assert!(has(
+        "DESCRIPTION:教师：张老师\\n教学班：TC-1\\n1-16周 星期一"
+    ));
+    assert!(lines.iter().any(|l| l.starts_with("DTSTAMP:")));
+    assert!(has("TZID:Asia/Shanghai"));
+}
+
+#[test]
+fn odd_even_and_irregular_weeks() {
+    let mut calendar = calendar();
+    let place = "1-15周(单) 星期三 第3-4节 中2-3205,2-8双周 星期四 第9节 中2-3205,\
+                 1-4,6-8周 星期五 第5-6节 中2-3205";
+    let class = class("TC-3", place).chosen().build();
+    calendar.add_class("高等数学", &class);
+    let lines = unfold(&calendar.to_ics());
+    let has = |line: &str| lines.iter().any(|l| l == line);
+
+    assert!(has("DTSTART;TZID=Asia/Shanghai:20250903T101000"));
+    assert!(has("DTEND;TZID=Asia/Shanghai:20250903T120000"));
+    assert!(has("RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=8"));
+
+    // 第二周的星期四
+    assert!(has("DTSTART;TZID=Asia/Shanghai:20250911T191000"));
+    assert!(has("RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=4"));
+
+    assert!(has("DTSTART;TZID=Asia/Shanghai:20250905T140000"));
+    assert!(has("DTEND;TZID=Asia/Shanghai:20250905T155000"));
+    assert!(has("RRULE:FREQ=WEEKLY;COUNT=8"));
+    // 第五周的星期五不上课
+    assert!(has("EXDATE;TZID=Asia/Shanghai:20251003T140000"));
+}
+
+#[test]
+fn escapes_and_folds_long_lines() {
+    let mut calendar = calendar();
+    let name = "经济学原理,第二部分;含实验\\讨论".repeat(3);
+    let class = class("TC-4", "1-16周 星期一 第1-2节 主楼A-101")
+        .chosen()
+        .build();
+    calendar.add_class(&name, &class);
+    let ics = calendar.to_ics();
+    for line in ics.split("\r\n") {
+        assert!(line.len() <= 75, "{line}");
+    }
+    let summary = unfold(&ics)
+        .into_iter()
+        .find(|l| l.starts_with("SUMMARY:"))
+        .unwrap();
+    assert_eq!(
+        summary,
+        format!(
+            "SUMMARY:{}",
+            r"经济学原理\,第二部分\;含实验\\讨论".repeat(3)
+        )
+    );
+}
+
+#[test]
+fn skips_what_it_cannot_place() {
+    let mut calendar = calendar();
+    let class = class("TC-5", "待定,1-16周 星期一 第12-13节 主楼A-101")
+        .chosen()
+        .build();
+    calendar.add_class("形势与政策", &class);
+    assert_eq!(calendar.skipped().len(), 2);
+    assert_eq!(
+        calendar.skipped()[0],
+        ("TC-5".to_string(), "待定".to_string())
+    );
+    assert!(!calendar.to_ics().contains("BEGIN:VEVENT"));
+}
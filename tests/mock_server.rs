@@ -0,0 +1,394 @@
+mod common;
+
+use common::{login_course, options};
+use std::sync::Arc;
+use xjtu_login::captcha::CaptchaImage;
+use xjtu_login::course::{CourseError, CourseSession, CourseType, VolunteerOutcome};
+use xjtu_login::login::{self, LoginError, LoginOptions, Service};
+use xjtu_login::managed::{Credentials, ManagedCourseSession};
+use xjtu_login::mfa::{MfaChallenge, MfaHandler};
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+use xjtu_login::sso::CasSession;
+
+fn scenario() -> Scenario {
+    let mut full = MockClass::new("TC-2", "ECON2001", "国际结算", 30);
+    full.selected = 30;
+    Scenario {
+        classes: vec![
+            MockClass::new("TC-1", "ECON2001", "国际结算", 30),
+            full,
+            MockClass::new("TC-3", "MATH1001", "高等数学", 100),
+        ],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn login_and_select_course() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let login = login_course(&server, &options(&server)).await;
+    let session = CourseSession::from_login(&login).await.unwrap();
+    assert_eq!(session.number, "2200000000");
+    assert_eq!(session.name, "测试学生");
+
+    let batches = session.get_batch_list().await.unwrap();
+    assert_eq!(batches.len(), 1);
+    let courses = session
+        .list_course(&batches[0], CourseType::TJKC, 0, "国际结算")
+        .await
+        .unwrap();
+    assert_eq!(courses.len(), 1);
+    assert_eq!(courses[0].tc_list.len(), 2);
+    assert!(courses[0].tc_list[1].is_full);
+
+    session
+        .add_volunteer(&batches[0], "TC-1", CourseType::TJKC)
+        .await
+        .unwrap();
+    assert_eq!(
+        session
+            .get_capacity("TC-1")
+            .await
+            .unwrap()
+            .number_of_selected,
+        1
+    );
+    assert!(server.scenario().classes[0].chosen);
+
+    session.delete_volunteer(&batches[0], "TC-1").await.unwrap();
+    assert_eq!(
+        session
+            .get_capacity("TC-1")
+            .await
+            .unwrap()
+            .number_of_selected,
+        0
+    );
+}
+
+#[tokio::test]
+async fn full_class_is_not_selected() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let login = login_course(&server, &options(&server)).await;
+    let session = CourseSession::from_login(&login).await.unwrap();
+    let batches = session.get_batch_list().await.unwrap();
+
+    let outcome = session
+        .add_volunteer(&batches[0], "TC-2", CourseType::TJKC)
+        .await
+        .unwrap();
+    assert_eq!(outcome, VolunteerOutcome::Full);
+    let capacity = session.get_capacity("TC-2").await.unwrap();
+    assert_eq!(capacity.number_of_selected, 30);
+    assert!(!server.scenario().classes[1].chosen);
+}
+
+#[tokio::test]
+async fn wrong_password_is_rejected() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let result = login::login_with_options(
+        Service::CourseSelection,
+        "2200000000",
+        "wrong",
+        &options(&server),
+    )
+    .await;
+    assert!(matches!(result, Err(LoginError::BadCredentials(_))));
+    assert_eq!(server.form_logins(), 0);
+}
+
+#[tokio::test]
+async fn rejection_messages_are_classified() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    /// 错误信息、期望的错误类型、是否可以重试
+    type Case = (&'static str, fn(&LoginError) -> bool, bool);
+    let cases: [Case; 7] = [
+        (
+            "密码错误次数过多，账号已锁定",
+            |e| matches!(e, LoginError::AccountLocked(_)),
+            false,
+        ),
+        (
+            "登录过于频繁，请稍后再试",
+            |e| matches!(e, LoginError::TooManyAttempts(_)),
+            false,
+        ),
+        (
+            "验证码已过期",
+            |e| matches!(e, LoginError::CaptchaRejected(_)),
+            true,
+        ),
+        (
+            "验证码已失效，请刷新",
+            |e| matches!(e, LoginError::CaptchaRejected(_)),
+            true,
+        ),
+        (
+            "密码已过期，请修改密码",
+            |e| matches!(e, LoginError::PasswordExpired(_)),
+            false,
+        ),
+        (
+            "用户名或密码错误",
+            |e| matches!(e, LoginError::BadCredentials(_)),
+            false,
+        ),
+        ("系统繁忙", |e| matches!(e, LoginError::Rejected(_)), true),
+    ];
+    for (message, expected, retryable) in cases {
+        server.update(|s| s.login_error = Some(message.to_string()));
+        let error = login::login_with_options(
+            Service::CourseSelection,
+            "2200000000",
+            "password",
+            &options(&server),
+        )
+        .await
+        .err()
+        .expect("login should be rejected");
+        assert!(expected(&error), "{message}: {error:?}");
+        assert_eq!(error.is_retryable(), retryable, "{message}");
+    }
+}
+
+#[tokio::test]
+async fn captcha_is_required_after_failure() {
+    let server = MockServer::start(Scenario {
+        captcha: Some("x7k2".to_string()),
+        ..scenario()
+    })
+    .await
+    .unwrap();
+    // 登录页始终带有隐藏的验证码图片，没有失败过时不需要填写
+    login_course(&server, &options(&server)).await;
+
+    let result = login::login_with_options(
+        Service::CourseSelection,
+        "2200000000",
+        "wrong",
+        &options(&server),
+    )
+    .await;
+    assert!(matches!(result, Err(LoginError::BadCredentials(_))));
+    let result = login::login_with_options(
+        Service::CourseSelection,
+        "2200000000",
+        "password",
+        &options(&server),
+    )
+    .await;
+    assert!(matches!(result, Err(LoginError::CaptchaRequired)));
+
+    let solver = |image: CaptchaImage| async move {
+        assert_eq!(image.content_type.as_deref(), Some("image/jpeg"));
+        Some("x7k2".to_string())
+    };
+    login_course(
+        &server,
+        &LoginOptions {
+            captcha: Some(&solver),
+            ..options(&server)
+        },
+    )
+    .await;
+    assert_eq!(server.form_logins(), 2);
+}
+
+#[tokio::test]
+async fn mfa_required_without_handler() {
+    let server = MockServer::start(Scenario {
+        mfa_code: Some("123456".to_string()),
+        ..scenario()
+    })
+    .await
+    .unwrap();
+    let result = login::login_with_options(
+        Service::CourseSelection,
+        "2200000000",
+        "password",
+        &options(&server),
+    )
+    .await;
+    assert!(matches!(result, Err(LoginError::MfaRequired)));
+}
+
+#[tokio::test]
+async fn mfa_code_is_verified() {
+    let server = MockServer::start(Scenario {
+        mfa_code: Some("123456".to_string()),
+        ..scenario()
+    })
+    .await
+    .unwrap();
+
+    let wrong = |_: MfaChallenge| async { Some("000000".to_string()) };
+    let result = login::login_with_options(
+        Service::CourseSelection,
+        "2200000000",
+        "password",
+        &LoginOptions {
+            mfa: Some(&wrong),
+            ..options(&server)
+        },
+    )
+    .await;
+    assert!(matches!(result, Err(LoginError::MfaRejected(_))));
+
+    let right = |challenge: MfaChallenge| async move {
+        assert_eq!(challenge.target, "138****0000");
+        Some("123456".to_string())
+    };
+    let login = login_course(
+        &server,
+        &LoginOptions {
+            mfa: Some(&right),
+            ..options(&server)
+        },
+    )
+    .await;
+    assert!(CourseSession::from_login(&login).await.is_ok());
+}
+
+#[tokio::test]
+async fn expired_token_triggers_relogin() {
+    let server = MockServer::start(Scenario {
+        mfa_code: Some("123456".to_string()),
+        ..scenario()
+    })
+    .await
+    .unwrap();
+    let mfa: Arc<dyn MfaHandler> = Arc::new(|_: MfaChallenge| async { Some("123456".to_string()) });
+    let credentials = Credentials {
+        mfa: Some(mfa),
+        endpoints: server.endpoints(),
+        ..Credentials::new("2200000000", "password")
+    };
+    let managed = ManagedCourseSession::login(credentials).await.unwrap();
+    let first = managed.session().await;
+    assert_eq!(managed.get_batch_list().await.unwrap().len(), 1);
+
+    server.expire_course_sessions();
+    assert!(!first.is_alive().await);
+    assert_eq!(managed.get_batch_list().await.unwrap().len(), 1);
+    assert!(!Arc::ptr_eq(&first, &managed.session().await));
+    assert_eq!(server.form_logins(), 2);
+}
+
+#[tokio::test]
+async fn cas_session_logs_in_without_password() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let cas = CasSession::login("2200000000", "password", &options(&server))
+        .await
+        .unwrap();
+    let login = cas.login_service(Service::CourseSelection).await.unwrap();
+    assert!(CourseSession::from_login(&login).await.is_ok());
+    assert_eq!(server.form_logins(), 1);
+    // 每次登录服务只颁发一张票据
+    assert_eq!(server.unused_tickets(), 0);
+    server.expire_course_sessions();
+    cas.login_service(Service::CourseSelection).await.unwrap();
+    assert_eq!(server.unused_tickets(), 0);
+
+    server.expire_cas_sessions();
+    server.expire_course_sessions();
+    let result = cas.login_service(Service::CourseSelection).await;
+    assert!(matches!(result, Err(LoginError::SsoExpired)));
+}
+
+#[tokio::test]
+async fn service_ticket_is_kept_whatever_the_service_returns() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let service = server.portal_service();
+    let ticket = login::service_ticket(&service, "2200000000", "password", &options(&server))
+        .await
+        .unwrap();
+    assert!(ticket.ticket.starts_with("ST-"));
+    assert!(ticket.callback_url.starts_with(&service));
+    // 服务接受了票据并重定向到一个 404 页面，停在回调处而不是报错
+    assert_eq!(ticket.landing.status(), 302);
+
+    let cas = CasSession::login("2200000000", "password", &options(&server))
+        .await
+        .unwrap();
+    let ticket = cas.service_ticket(&service).await.unwrap();
+    assert!(ticket.ticket.starts_with("ST-"));
+    assert_eq!(ticket.landing.status(), 302);
+}
+
+#[tokio::test]
+async fn server_error_carries_message() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let login = login_course(&server, &options(&server)).await;
+    let session = CourseSession::from_login(&login).await.unwrap();
+    match session.get_capacity("TC-404").await {
+        Err(CourseError::ServerError(code, msg)) => {
+            assert_eq!(code, "0");
+            assert_eq!(msg, "教学班不存在");
+        }
+        _ => panic!("expected a server error"),
+    }
+}
+
+#[tokio::test]
+async fn maintenance_page_is_not_json() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let login = login_course(&server, &options(&server)).await;
+    let session = CourseSession::from_login(&login).await.unwrap();
+    // 第 200 个字节落在汉字中间
+    let page = format!("<html>{}</html>", "选课系统维护中".repeat(40));
+    server.update(|s| s.maintenance = Some(page));
+    match session.get_batch_list().await {
+        Err(CourseError::NotJson(url, body)) => {
+            assert!(url.ends_with("batch.do"));
+            assert!(body.starts_with("<html>选课系统维护中"));
+            assert!(body.ends_with(" truncated)"));
+        }
+        _ => panic!("expected a non-JSON error"),
+    }
+
+    server.update(|s| s.maintenance = None);
+    assert_eq!(session.get_batch_list().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn volunteer_outcomes() {
+    let mut conflicting = MockClass::new("TC-4", "PHYS1001", "大学物理", 50);
+    conflicting.conflict = true;
+    let mut heavy = MockClass::new("TC-5", "CHEM1001", "大学化学", 50);
+    heavy.credit = 4;
+    let mut scenario = scenario();
+    scenario.classes.extend([conflicting, heavy]);
+    scenario.credit_limit = Some(5);
+    let server = MockServer::start(scenario).await.unwrap();
+    let login = login_course(&server, &options(&server)).await;
+    let session = CourseSession::from_login(&login).await.unwrap();
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let add = |class_id: &'static str| session.add_volunteer(batch, class_id, CourseType::TJKC);
+
+    assert_eq!(add("TC-1").await.unwrap(), VolunteerOutcome::Success);
+    assert_eq!(
+        add("TC-1").await.unwrap(),
+        VolunteerOutcome::AlreadySelected
+    );
+    assert_eq!(add("TC-4").await.unwrap(), VolunteerOutcome::Conflict);
+    assert_eq!(
+        add("TC-5").await.unwrap(),
+        VolunteerOutcome::CreditLimitExceeded
+    );
+
+    server.update(|s| s.selection_open = false);
+    assert_eq!(add("TC-3").await.unwrap(), VolunteerOutcome::NotInPeriod);
+    assert_eq!(
+        session.delete_volunteer(batch, "TC-1").await.unwrap(),
+        VolunteerOutcome::NotInPeriod
+    );
+
+    server.update(|s| s.selection_open = true);
+    let outcome = session.delete_volunteer(batch, "TC-1").await.unwrap();
+    assert!(outcome.is_success());
+    let outcome = session.delete_volunteer(batch, "TC-1").await.unwrap();
+    assert_eq!(
+        outcome,
+        VolunteerOutcome::Unknown("未选择该教学班".to_string())
+    );
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use common::managed;
+use xjtu_login::course::VolunteerOutcome;
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+use xjtu_login::plan::{Plan, PlanOutcome, Preference};
+
+const PLAN: &str = r#"
+[[course]]
+course_number = "ECON2001"
+course_type = "TJKC"
+prefer = [{ teacher = "张老师" }, { teacher = "王老师" }]
+
+[[course]]
+course_number = "MATH1001"
+course_type = "TJKC"
+any = false
+prefer = [{ class_id = "TC-9" }]
+
+[[course]]
+course_number = "CHEM1001"
+course_type = "TJKC"
+
+[[course]]
+course_number = "HIST1001"
+course_type = "TJKC"
+
+[[course]]
+course_number = "PHYS1001"
+course_type = "TJKC"
+"#;
+
+fn class(id: &str, number: &str, teacher: &str) -> MockClass {
+    let mut class = MockClass::new(id, number, number, 30);
+    class.teacher_name = teacher.to_string();
+    class
+}
+
+fn scenario() -> Scenario {
+    let mut full = class("TC-1", "ECON2001", "张老师");
+    full.selected = 30;
+    let mut conflicting = class("TC-9", "MATH1001", "赵老师");
+    conflicting.conflict = true;
+    let mut heavy = class("TC-5", "CHEM1001", "钱老师");
+    heavy.credit = 4;
+    let mut chosen = class("TC-7", "HIST1001", "孙老师");
+    chosen.chosen = true;
+    chosen.selected = 1;
+    Scenario {
+        classes: vec![
+            full,
+            class("TC-2", "ECON2001", "李老师"),
+            class("TC-3", "ECON2001", "王老师"),
+            conflicting,
+            class("TC-10", "MATH1001", "周老师"),
+            heavy,
+            chosen,
+        ],
+        credit_limit: Some(5),
+        ..Default::default()
+    }
+}
+
+fn write_temp(name: &str, text: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("xjtu-login-{}-{name}", std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn loads_toml_and_json() {
+    let path = write_temp("plan.toml", PLAN);
+    let plan = Plan::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(plan.courses.len(), 5);
+    assert_eq!(
+        plan.courses[0].prefer,
+        vec![
+            Preference::Teacher("张老师".to_string()),
+            Preference::Teacher("王老师".to_string())
+        ]
+    );
+    assert!(plan.courses[0].any);
+    assert!(!plan.courses[1].any);
+
+    let path = write_temp("plan.json", &serde_json::to_string(&plan).unwrap());
+    let reloaded = Plan::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(reloaded.courses[1].prefer, plan.courses[1].prefer);
+
+    let path = write_temp("broken.toml", "[[course]]\ncourse_type = \"TJKC\"\n");
+    assert!(Plan::load(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn executes_in_priority_order() {
+    let server = MockServer::start(scenario()).await.unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let plan: Plan = toml::from_str(PLAN).unwrap();
+
+    // 计划开始前会话已过期，执行时自动重新登录
+    server.expire_course_sessions();
+    let results = plan.execute(&session, batch).await.unwrap();
+    let outcomes: Vec<_> = results.iter().map(|r| r.outcome.clone()).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            // 张老师的班已满，跳到王老师的班，而不是列表中更靠前的李老师的班
+            PlanOutcome::Obtained("TC-3".to_string()),
+            // 指定的班冲突，且不接受其他教学班
+            PlanOutcome::Failed(Vec::new()),
+            PlanOutcome::Failed(vec![(
+                "TC-5".to_string(),
+                VolunteerOutcome::CreditLimitExceeded
+            )]),
+            PlanOutcome::AlreadySelected("TC-7".to_string()),
+            PlanOutcome::NotFound,
+        ]
+    );
+    assert_eq!(results[0].course_number, "ECON2001");
+    let chosen: Vec<String> = server
+        .scenario()
+        .classes
+        .into_iter()
+        .filter(|c| c.chosen)
+        .map(|c| c.id)
+        .collect();
+    assert_eq!(chosen, vec!["TC-3".to_string(), "TC-7".to_string()]);
+}
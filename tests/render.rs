@@ -0,0 +1,51 @@
+use xjtu_login::course::{CapacityInfo, VolunteerOutcome};
+use xjtu_login::render::OutputFormat;
+
+fn capacity() -> CapacityInfo {
+    CapacityInfo {
+        number_of_male: 10,
+        capacity_of_male: 15,
+        number_of_female: 5,
+        capacity_of_female: 15,
+        number_of_selected: 15,
+        class_capacity: 30,
+    }
+}
+
+#[test]
+fn table_aligns_wide_characters() {
+    let out = OutputFormat::Table.renderer().capacity("TC-1", &capacity());
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3);
+    // 表头的“教学班”占 6 列，与数据行的 “TC-1” 补齐到同一宽度
+    assert!(lines[0].starts_with("教学班  男生"));
+    assert!(lines[1].starts_with("------  -----"));
+    assert!(lines[2].starts_with("TC-1    10/15"));
+}
+
+#[test]
+fn formats_are_parsed() {
+    assert_eq!("JSON".parse::<OutputFormat>(), Ok(OutputFormat::Json));
+    assert_eq!("plain".parse::<OutputFormat>(), Ok(OutputFormat::Plain));
+    assert!("xml".parse::<OutputFormat>().is_err());
+}
+
+#[test]
+fn json_is_machine_readable() {
+    let renderer = OutputFormat::Json.renderer();
+    let value: serde_json::Value =
+        serde_json::from_str(&renderer.capacity("TC-1", &capacity())).unwrap();
+    assert_eq!(value["classId"], "TC-1");
+    assert_eq!(value["capacity"]["number_of_selected"], 15);
+    let value: serde_json::Value =
+        serde_json::from_str(&renderer.outcome("TC-1", &VolunteerOutcome::Full)).unwrap();
+    assert_eq!(value["outcome"], "Full");
+}
+
+#[test]
+fn plain_is_one_line_per_record() {
+    let out = OutputFormat::Plain
+        .renderer()
+        .outcome("TC-1", &VolunteerOutcome::Success);
+    assert_eq!(out, "TC-1 - 成功\n");
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use chrono::{FixedOffset, TimeDelta, Timelike, Utc};
+use common::managed;
+use std::time::{Duration, Instant};
+use xjtu_login::course::{CourseType, VolunteerOutcome, parse_server_time};
+use xjtu_login::managed::ManagedCourseSession;
+use xjtu_login::mock::{MockBatch, MockClass, MockServer, Scenario};
+use xjtu_login::schedule::{ScheduleConfig, Scheduler, sync_clock};
+use xjtu_login::sniper::Target;
+
+/// 服务器时间 `delay` 之后的北京时间字符串
+fn server_time_after(skew: TimeDelta, delay: TimeDelta) -> String {
+    let beijing = FixedOffset::east_opt(8 * 3600).unwrap();
+    (Utc::now() + skew + delay)
+        .with_timezone(&beijing)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+async fn start(skew: TimeDelta, begin_time: String) -> (MockServer, ManagedCourseSession) {
+    let server = MockServer::start(Scenario {
+        clock_skew: skew,
+        batches: vec![MockBatch {
+            begin_time,
+            ..MockBatch::new("B1", "2025-2026学年第一学期正选")
+        }],
+        classes: vec![MockClass::new("TC-1", "ECON2001", "国际结算", 30)],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    (server, session)
+}
+
+#[test]
+fn parses_batch_times_as_beijing_time() {
+    let time = parse_server_time("2025-09-01 08:00:00").unwrap();
+    assert_eq!(time.with_timezone(&Utc).hour(), 0);
+    assert!(parse_server_time("2025/09/01 08:00").is_err());
+}
+
+#[tokio::test]
+async fn estimates_clock_offset() {
+    let skew = TimeDelta::milliseconds(-42_300);
+    let (_server, session) = start(skew, "2025-01-01 08:00:00".to_string()).await;
+    let clock = sync_clock(&session, 10, Duration::from_millis(110))
+        .await
+        .unwrap();
+    let error = (clock.offset - skew).abs();
+    assert!(
+        error < TimeDelta::milliseconds(300),
+        "offset {:?}",
+        clock.offset
+    );
+    assert!(clock.uncertainty < Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn fires_when_batch_opens() {
+    // 服务器时钟比本地慢 20 秒：按本地时钟看批次早已开放，直接选课会被拒绝
+    let skew = TimeDelta::seconds(-20);
+    let (server, session) = start(skew, server_time_after(skew, TimeDelta::seconds(3))).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let started = Instant::now();
+    let report = Scheduler::new(&session, batch, vec![Target::new("TC-1", CourseType::TJKC)])
+        .with_config(ScheduleConfig {
+            clock_samples: 6,
+            sample_spacing: Duration::from_millis(170),
+            lead: Duration::from_millis(50),
+            burst_window: Duration::from_secs(3),
+            ..Default::default()
+        })
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.obtained, vec!["TC-1".to_string()]);
+    assert!(report.missed.is_empty());
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert!(report.rounds <= 15, "took {} rounds", report.rounds);
+    assert!(server.scenario().classes[0].chosen);
+}
+
+#[tokio::test]
+async fn misses_are_reported() {
+    let (_server, session) = start(TimeDelta::zero(), "2099-01-01 08:00:00".to_string()).await;
+    let mut batch = session.get_batch_list().await.unwrap().remove(0);
+    // 本地看来已经开放，服务器却要到 2099 年才开放
+    batch.begin_time = "2025-01-01 08:00:00".to_string();
+    let report = Scheduler::new(
+        &session,
+        &batch,
+        vec![Target::new("TC-1", CourseType::TJKC)],
+    )
+    .with_config(ScheduleConfig {
+        clock_samples: 1,
+        burst_window: Duration::from_millis(300),
+        ..Default::default()
+    })
+    .run()
+    .await
+    .unwrap();
+    assert!(report.obtained.is_empty());
+    assert_eq!(report.missed, vec!["TC-1".to_string()]);
+    assert!(report.rounds > 1);
+}
+
+#[tokio::test]
+async fn other_class_of_held_course_is_abandoned() {
+    let (server, session) = start(TimeDelta::zero(), "2025-01-01 08:00:00".to_string()).await;
+    server.update(|s| {
+        s.classes[0].chosen = true;
+        s.classes[0].selected = 1;
+        s.classes
+            .push(MockClass::new("TC-2", "ECON2001", "国际结算", 30));
+    });
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let report = Scheduler::new(
+        &session,
+        batch,
+        vec![
+            Target::new("TC-1", CourseType::TJKC),
+            Target::new("TC-2", CourseType::TJKC),
+        ],
+    )
+    .with_config(ScheduleConfig {
+        clock_samples: 1,
+        burst_window: Duration::from_millis(300),
+        ..Default::default()
+    })
+    .run()
+    .await
+    .unwrap();
+    // 只有已选的正是目标教学班才算选上
+    assert_eq!(report.obtained, vec!["TC-1".to_string()]);
+    assert_eq!(
+        report.abandoned,
+        vec![("TC-2".to_string(), VolunteerOutcome::AlreadySelected)]
+    );
+    assert!(report.missed.is_empty());
+    assert!(!server.scenario().classes[1].chosen);
+}
+
+#[tokio::test]
+async fn huge_prewarm_starts_at_once() {
+    let (server, session) = start(TimeDelta::zero(), "2025-01-01 08:00:00".to_string()).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let report = Scheduler::new(&session, batch, vec![Target::new("TC-1", CourseType::TJKC)])
+        .with_config(ScheduleConfig {
+            clock_samples: 1,
+            prewarm: Duration::MAX,
+            lead: Duration::MAX,
+            ..Default::default()
+        })
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.obtained, vec!["TC-1".to_string()]);
+    assert!(server.scenario().classes[0].chosen);
+}
@@ -0,0 +1,210 @@
+mod common;
+
+use common::managed;
+use std::time::Duration;
+use xjtu_login::course::CourseType;
+use xjtu_login::course::{CapacityInfo, Gender, VolunteerOutcome};
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+use xjtu_login::sniper::{Sniper, SniperConfig, StopReason, StopWhen, Target};
+
+fn full_class(id: &str) -> MockClass {
+    let mut class = MockClass::new(id, "ECON2001", "国际结算", 2);
+    class.selected = 2;
+    class
+}
+
+fn config() -> SniperConfig {
+    SniperConfig {
+        interval: Duration::from_millis(20),
+        jitter: Duration::from_millis(5),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn grabs_seat_when_freed() {
+    let server = MockServer::start(Scenario {
+        classes: vec![full_class("TC-1"), full_class("TC-2")],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let targets = vec![
+        Target::new("TC-1", CourseType::TJKC),
+        Target::new("TC-2", CourseType::TJKC),
+    ];
+    let sniper = Sniper::new(&session, batch, targets).with_config(config());
+
+    let free_seat = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        server.update(|s| s.classes[1].selected -= 1);
+    };
+    let (report, _) = tokio::join!(sniper.run(), free_seat);
+    let report = report.unwrap();
+    assert_eq!(report.reason, StopReason::Obtained);
+    assert_eq!(report.obtained, vec!["TC-2".to_string()]);
+    assert!(report.rounds > 1);
+    let scenario = server.scenario();
+    assert!(!scenario.classes[0].chosen);
+    assert!(scenario.classes[1].chosen);
+}
+
+#[tokio::test]
+async fn keeps_going_through_maintenance() {
+    let server = MockServer::start(Scenario {
+        classes: vec![full_class("TC-1")],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    server.update(|s| s.maintenance = Some("<html>选课系统维护中</html>".to_string()));
+    let sniper = Sniper::new(&session, batch, vec![Target::new("TC-1", CourseType::TJKC)])
+        .with_config(SniperConfig {
+            timeout: Some(Duration::from_secs(5)),
+            ..config()
+        });
+
+    let recover = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        server.update(|s| {
+            s.maintenance = None;
+            s.classes[0].selected -= 1;
+        });
+    };
+    let (report, _) = tokio::join!(sniper.run(), recover);
+    let report = report.unwrap();
+    assert_eq!(report.reason, StopReason::Obtained);
+    assert_eq!(report.obtained, vec!["TC-1".to_string()]);
+    assert!(report.rounds > 1);
+}
+
+#[tokio::test]
+async fn stops_after_max_rounds() {
+    let server = MockServer::start(Scenario {
+        classes: vec![full_class("TC-1")],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let report = Sniper::new(&session, batch, vec![Target::new("TC-1", CourseType::TJKC)])
+        .with_config(SniperConfig {
+            max_rounds: Some(3),
+            ..config()
+        })
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.reason, StopReason::MaxRounds);
+    assert_eq!(report.rounds, 3);
+    assert!(report.obtained.is_empty());
+}
+
+#[tokio::test]
+async fn abandons_conflicting_class() {
+    let mut conflicting = MockClass::new("TC-1", "PHYS1001", "大学物理", 50);
+    conflicting.conflict = true;
+    let server = MockServer::start(Scenario {
+        classes: vec![
+            conflicting,
+            MockClass::new("TC-2", "MATH1001", "高等数学", 50),
+        ],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let targets = vec![
+        Target::new("TC-1", CourseType::TJKC),
+        Target::new("TC-2", CourseType::TJKC),
+    ];
+    let report = Sniper::new(&session, batch, targets)
+        .with_config(SniperConfig {
+            stop_when: StopWhen::All,
+            timeout: Some(Duration::from_secs(5)),
+            ..config()
+        })
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.reason, StopReason::Obtained);
+    assert_eq!(report.obtained, vec!["TC-2".to_string()]);
+    assert_eq!(
+        report.abandoned,
+        vec![("TC-1".to_string(), VolunteerOutcome::Conflict)]
+    );
+}
+
+#[tokio::test]
+async fn other_class_of_held_course_is_not_obtained() {
+    let mut held = MockClass::new("TC-1", "ECON2001", "国际结算", 30);
+    held.chosen = true;
+    held.selected = 1;
+    let server = MockServer::start(Scenario {
+        classes: vec![held, MockClass::new("TC-2", "ECON2001", "国际结算", 30)],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+
+    // 已选 TC-1 时，TC-2 被拒绝为"该课程已选"，不能算作抢到
+    let report = Sniper::new(&session, batch, vec![Target::new("TC-2", CourseType::TJKC)])
+        .with_config(config())
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.reason, StopReason::Exhausted);
+    assert!(report.obtained.is_empty());
+    assert_eq!(
+        report.abandoned,
+        vec![("TC-2".to_string(), VolunteerOutcome::AlreadySelected)]
+    );
+    assert!(!server.scenario().classes[1].chosen);
+
+    // 已选的正是目标教学班时视为抢到
+    let report = Sniper::new(&session, batch, vec![Target::new("TC-1", CourseType::TJKC)])
+        .with_config(config())
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.reason, StopReason::Obtained);
+    assert_eq!(report.obtained, vec!["TC-1".to_string()]);
+}
+
+#[test]
+fn gender_sub_capacity() {
+    let capacity = CapacityInfo {
+        number_of_male: 10,
+        capacity_of_male: 10,
+        number_of_female: 5,
+        capacity_of_female: 10,
+        number_of_selected: 15,
+        class_capacity: 20,
+    };
+    assert!(capacity.has_seat(None));
+    assert!(!capacity.has_seat(Some(Gender::Male)));
+    assert!(capacity.has_seat(Some(Gender::Female)));
+
+    let unsplit = CapacityInfo {
+        number_of_male: 0,
+        capacity_of_male: 0,
+        number_of_female: 0,
+        capacity_of_female: 0,
+        number_of_selected: 19,
+        class_capacity: 20,
+    };
+    assert!(unsplit.has_seat(Some(Gender::Male)));
+    let full = CapacityInfo {
+        number_of_selected: 20,
+        ..unsplit
+    };
+    assert!(!full.has_seat(None));
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{class, course};
+use xjtu_login::course::CourseType;
+use xjtu_login::sniper::Target;
+use xjtu_login::solver::{Preferences, Solver};
+use xjtu_login::timetable::{Timetable, parse_teaching_place};
+
+/// 只比较冲突与排序时不考虑时间偏好
+fn neutral() -> Preferences {
+    Preferences {
+        early_penalty: 0.0,
+        gap_penalty: 0.0,
+        day_penalty: 0.0,
+        ..Preferences::default()
+    }
+}
+
+fn class_ids(solver: &Solver, top: usize) -> Vec<Vec<String>> {
+    solver
+        .solve(top)
+        .into_iter()
+        .map(|s| s.choices.into_iter().map(|c| c.class_id).collect())
+        .collect()
+}
+
+#[test]
+fn skips_conflicting_combinations() {
+    let a = course(
+        "A",
+        vec![
+            class("A1", "1-16周 星期一 第1-2节").teacher("张老师"),
+            class("A2", "1-16周 星期二 第3-4节").teacher("李老师"),
+        ],
+    );
+    let b = course(
+        "B",
+        vec![
+            class("B1", "1-16周 星期一 第1-2节").teacher("王老师"),
+            class("B2", "1-8周 星期二 第3-4节").teacher("赵老师"),
+            class("B3", "9-16周 星期二 第3-4节").teacher("钱老师"),
+        ],
+    );
+    let mut solver = Solver::new(neutral());
+    solver.add_course(&a, CourseType::TJKC);
+    solver.add_course(&b, CourseType::XGXK);
+    let mut found = class_ids(&solver, 10);
+    found.sort();
+    assert_eq!(
+        found,
+        vec![vec!["A1", "B2"], vec!["A1", "B3"], vec!["A2", "B1"]]
+    );
+
+    let solution = &solver.solve(1)[0];
+    assert_eq!(solution.choices[1].course_number, "B");
+    let targets: Vec<(String, CourseType)> = solution
+        .targets()
+        .into_iter()
+        .map(|t: Target| (t.class_id, t.course_type))
+        .collect();
+    assert_eq!(
+        targets,
+        vec![
+            (solution.choices[0].class_id.clone(), CourseType::TJKC),
+            (solution.choices[1].class_id.clone(), CourseType::XGXK),
+        ]
+    );
+}
+
+#[test]
+fn ranks_by_teacher_and_capacity() {
+    let a = course(
+        "A",
+        vec![
+            class("A1", "1-16周 星期一 第3-4节").teacher("张老师"),
+            class("A2", "1-16周 星期二 第3-4节")
+                .teacher("李老师")
+                .full(),
+            class("A3", "1-16周 星期三 第3-4节").teacher("李老师,王老师"),
+        ],
+    );
+    let mut solver = Solver::new(Preferences {
+        teachers: vec!["李老师".into()],
+        ..neutral()
+    });
+    solver.add_course(&a, CourseType::TJKC);
+    // 偏好的教师优先，但已满的教学班排在最后
+    assert_eq!(class_ids(&solver, 3), vec![["A3"], ["A1"], ["A2"]]);
+    assert_eq!(class_ids(&solver, 1), vec![["A3"]]);
+}
+
+#[test]
+fn prefers_late_and_compact_days() {
+    let a = course(
+        "A",
+        vec![
+            class("A1", "1-16周 星期一 第1-2节").teacher("张老师"),
+            class("A2", "1-16周 星期一 第3-4节").teacher("张老师"),
+            class("A3", "1-16周 星期二 第3-4节").teacher("张老师"),
+        ],
+    );
+    let b = course(
+        "B",
+        vec![class("B1", "1-16周 星期一 第5-6节").teacher("李老师")],
+    );
+    let mut solver = Solver::new(Preferences::default());
+    solver.add_course(&a, CourseType::TJKC);
+    solver.add_course(&b, CourseType::TJKC);
+    // A2 与 B1 连堂；A1 中间空两节且要早起；A3 多占一天
+    assert_eq!(
+        class_ids(&solver, 3),
+        vec![["A2", "B1"], ["A3", "B1"], ["A1", "B1"]]
+    );
+    let scores: Vec<f64> = solver.solve(3).iter().map(|s| s.score).collect();
+    assert_eq!(scores, vec![-1.0, -2.0, -5.0]);
+}
+
+#[test]
+fn respects_fixed_timetable() {
+    let a = course(
+        "A",
+        vec![
+            class("A1", "1-16周 星期一 第3-4节").teacher("张老师"),
+            class("A2", "1-16周 星期二 第3-4节").teacher("李老师"),
+        ],
+    );
+    let mut fixed = Timetable::new();
+    fixed.add("X1", parse_teaching_place("1-16周 星期一 第3-4节"));
+    let mut solver = Solver::new(neutral()).with_timetable(fixed);
+    solver.add_course(&a, CourseType::TJKC);
+    assert_eq!(class_ids(&solver, 5), vec![["A2"]]);
+
+    let b = course(
+        "B",
+        vec![class("B1", "1-16周 星期二 第4-5节").teacher("王老师")],
+    );
+    solver.add_course(&b, CourseType::TJKC);
+    assert!(solver.solve(5).is_empty());
+}
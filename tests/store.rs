@@ -0,0 +1,142 @@
+mod common;
+
+use common::{course_session, login_course, options};
+use std::path::{Path, PathBuf};
+use xjtu_login::course::{CourseSession, CourseType};
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+use xjtu_login::store::SessionStore;
+
+async fn start() -> MockServer {
+    MockServer::start(Scenario {
+        classes: vec![MockClass::new("TC-1", "ECON2001", "国际结算", 30)],
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("xjtu-login-{}-{name}", std::process::id()))
+}
+
+/// 会话文件中保存的选课 token
+fn saved_token(path: &Path) -> String {
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    saved["course"]["token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn saves_and_restores_session() {
+    let server = start().await;
+    let login = login_course(&server, &options(&server)).await;
+    let session = CourseSession::from_login(&login).await.unwrap();
+    let path = temp_path("store-round-trip.json");
+    let store = SessionStore::new(&path);
+    store.clear().unwrap();
+    assert!(store.load().unwrap().is_none());
+
+    // 已存在的文件也要收紧权限
+    std::fs::write(&path, "").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    }
+    store.save(&login, Some(&session)).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let stored = store.load().unwrap().unwrap();
+    assert_eq!(stored.login.endpoints, server.endpoints());
+    assert!(!stored.login.webvpn);
+    let restored = stored.course_session().await.unwrap();
+    assert_eq!(restored.number, session.number);
+    let batch = &restored.get_batch_list().await.unwrap()[0];
+    let courses = restored
+        .list_course(batch, CourseType::TJKC, 0, "")
+        .await
+        .unwrap();
+    assert_eq!(courses.len(), 1);
+
+    store.clear().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn reuses_live_session_and_replaces_dead_one() {
+    let server = start().await;
+    let path = temp_path("store-reuse.json");
+    let store = SessionStore::new(&path);
+    store.clear().unwrap();
+    let options = options(&server);
+
+    let (_, session) = store
+        .course_session_or_login("2200000000", "password", &options)
+        .await
+        .unwrap();
+    let logins = server.form_logins();
+    assert_eq!(logins, 1);
+    let token = saved_token(&path);
+
+    // 保存的会话仍然有效，不再登录
+    let (_, restored) = store
+        .course_session_or_login("2200000000", "password", &options)
+        .await
+        .unwrap();
+    assert_eq!(restored.number, session.number);
+    assert_eq!(server.form_logins(), logins);
+    assert_eq!(saved_token(&path), token);
+
+    // 会话失效后重新登录，并保存新的会话
+    server.expire_course_sessions();
+    assert!(
+        store
+            .load()
+            .unwrap()
+            .unwrap()
+            .course_session()
+            .await
+            .is_none()
+    );
+    let (_, relogged) = store
+        .course_session_or_login("2200000000", "password", &options)
+        .await
+        .unwrap();
+    assert!(relogged.get_batch_list().await.is_ok());
+    assert_eq!(server.form_logins(), logins + 1);
+    assert_ne!(saved_token(&path), token);
+    assert!(
+        store
+            .load()
+            .unwrap()
+            .unwrap()
+            .course_session()
+            .await
+            .is_some()
+    );
+
+    store.clear().unwrap();
+}
+
+#[tokio::test]
+async fn unreadable_file_falls_back_to_login() {
+    let server = start().await;
+    let path = temp_path("store-corrupt.json");
+    std::fs::write(&path, "not json").unwrap();
+    let store = SessionStore::new(&path);
+    assert!(store.load().is_err());
+
+    let (_, session) = store
+        .course_session_or_login("2200000000", "password", &options(&server))
+        .await
+        .unwrap();
+    assert_eq!(session.number, course_session(&server).await.number);
+    assert!(store.load().unwrap().is_some());
+
+    store.clear().unwrap();
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{course_session, managed};
+use xjtu_login::course::{
+    CourseError, CourseType, Gender, GenderLimit, SwapOutcome, VolunteerOutcome,
+};
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+
+/// 已选 TC-1，同一课程的 TC-2 有空位
+fn classes() -> Vec<MockClass> {
+    let mut current = MockClass::new("TC-1", "ECON2001", "国际结算", 30);
+    current.chosen = true;
+    current.selected = 1;
+    vec![current, MockClass::new("TC-2", "ECON2001", "国际结算", 30)]
+}
+
+async fn swap(server: &MockServer) -> SwapOutcome {
+    let session = course_session(server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    session
+        .swap_class(batch, "TC-1", "TC-2", CourseType::TJKC, None)
+        .await
+        .unwrap()
+}
+
+fn chosen(server: &MockServer) -> Vec<bool> {
+    server.scenario().classes.iter().map(|c| c.chosen).collect()
+}
+
+async fn start(classes: Vec<MockClass>) -> MockServer {
+    MockServer::start(Scenario {
+        classes,
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn swaps_to_new_class() {
+    let server = start(classes()).await;
+    assert_eq!(swap(&server).await, SwapOutcome::Swapped);
+    assert_eq!(chosen(&server), vec![false, true]);
+}
+
+#[tokio::test]
+async fn full_target_is_left_alone() {
+    let mut classes = classes();
+    classes[1].selected = 30;
+    let server = start(classes).await;
+    assert_eq!(swap(&server).await, SwapOutcome::TargetFull);
+    assert_eq!(chosen(&server), vec![true, false]);
+}
+
+#[tokio::test]
+async fn failed_add_rolls_back() {
+    let mut classes = classes();
+    classes[1].conflict = true;
+    let server = start(classes).await;
+    assert_eq!(
+        swap(&server).await,
+        SwapOutcome::RolledBack(VolunteerOutcome::Conflict)
+    );
+    assert_eq!(chosen(&server), vec![true, false]);
+}
+
+#[tokio::test]
+async fn failed_rollback_is_reported() {
+    let mut classes = classes();
+    classes[0].conflict = true;
+    classes[1].conflict = true;
+    let server = start(classes).await;
+    assert_eq!(
+        swap(&server).await,
+        SwapOutcome::Lost(VolunteerOutcome::Conflict, VolunteerOutcome::Conflict)
+    );
+    assert_eq!(chosen(&server), vec![false, false]);
+}
+
+#[tokio::test]
+async fn checks_gender_quota() {
+    let mut classes = classes();
+    classes[1].limit_gender = GenderLimit::Female;
+    let server = start(classes).await;
+    let session = course_session(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let outcome = session
+        .swap_class(batch, "TC-1", "TC-2", CourseType::TJKC, Some(Gender::Male))
+        .await
+        .unwrap();
+    assert_eq!(outcome, SwapOutcome::TargetFull);
+    assert_eq!(chosen(&server), vec![true, false]);
+}
+
+#[tokio::test]
+async fn expiry_after_drop_is_reported() {
+    let mut classes = classes();
+    classes[1].conflict = true;
+    let server = MockServer::start(Scenario {
+        classes,
+        expire_on_delete: true,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = course_session(&server).await;
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let result = session
+        .swap_class(batch, "TC-1", "TC-2", CourseType::TJKC, None)
+        .await;
+    assert!(matches!(result, Err(CourseError::SessionExpired)));
+    assert_eq!(chosen(&server), vec![false, false]);
+}
+
+#[tokio::test]
+async fn managed_swap_relogins_after_drop() {
+    let mut classes = classes();
+    classes[1].conflict = true;
+    let server = MockServer::start(Scenario {
+        classes,
+        expire_on_delete: true,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let session = managed(&server).await;
+    let batch = session.get_batch_list().await.unwrap().remove(0);
+    // 换班可以放到后台任务中执行
+    let outcome = tokio::spawn(async move {
+        session
+            .swap_class(&batch, "TC-1", "TC-2", CourseType::TJKC, None)
+            .await
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(outcome, SwapOutcome::RolledBack(VolunteerOutcome::Conflict));
+    assert_eq!(chosen(&server), vec![true, false]);
+}
@@ -0,0 +1,263 @@
+mod common;
+
+use chrono::Weekday;
+use common::class;
+use xjtu_login::timetable::{Meeting, Parity, Timetable, Weeks, parse_teaching_place};
+
+fn weeks(list: &[u32]) -> Weeks {
+    let mut weeks = Weeks::default();
+    for week in list {
+        weeks.insert(*week);
+    }
+    weeks
+}
+
+fn only_meeting(text: &str) -> Meeting {
+    let schedule = parse_teaching_place(text);
+    assert!(
+        schedule.unparsed.is_empty(),
+        "{text}: {:?}",
+        schedule.unparsed
+    );
+    assert_eq!(schedule.meetings.len(), 1, "{text}");
+    schedule.meetings.into_iter().next().unwrap()
+}
+
+#[test]
+fn standard_format() {
+    let meeting = only_meeting("1-16周 星期一 第1-2节 主楼A-101");
+    assert_eq!(meeting.weeks, Weeks::range(1, 16, Parity::All));
+    assert_eq!(meeting.weekday, Weekday::Mon);
+    assert_eq!((meeting.first_period, meeting.last_period), (1, 2));
+    assert_eq!(meeting.campus, None);
+    assert_eq!(meeting.building.as_deref(), Some("主楼A"));
+    assert_eq!(meeting.room.as_deref(), Some("101"));
+    assert_eq!(meeting.to_string(), "1-16周 星期一 第1-2节 主楼A-101");
+}
+
+#[test]
+fn week_ranges() {
+    let cases: &[(&str, &[u32])] = &[
+        ("1-4,6-8周 星期二 第3-4节 中2-3205", &[1, 2, 3, 4, 6, 7, 8]),
+        ("1,3,5周 星期二 第3-4节 中2-3205", &[1, 3, 5]),
+        ("9周 星期二 第3-4节 中2-3205", &[9]),
+        ("1-7单周 星期二 第3-4节 中2-3205", &[1, 3, 5, 7]),
+        ("2-8双周 星期二 第3-4节 中2-3205", &[2, 4, 6, 8]),
+        ("1-7周(单) 星期二 第3-4节 中2-3205", &[1, 3, 5, 7]),
+        ("2-8周（双） 星期二 第3-4节 中2-3205", &[2, 4, 6, 8]),
+        ("1-5单,8-10周 星期二 第3-4节 中2-3205", &[1, 3, 5, 8, 9, 10]),
+        ("第1-3周 星期二 第3-4节 中2-3205", &[1, 2, 3]),
+        ("1~3周 星期二 第3-4节 中2-3205", &[1, 2, 3]),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(only_meeting(text).weeks, weeks(expected), "{text}");
+    }
+}
+
+#[test]
+fn weekdays_and_periods() {
+    let cases: &[(&str, Weekday, u8, u8)] = &[
+        ("1-16周 星期三 第5-6节 主楼B-204", Weekday::Wed, 5, 6),
+        ("1-16周 周五 第9-10节 主楼B-204", Weekday::Fri, 9, 10),
+        ("1-16周 星期日 第11节 主楼B-204", Weekday::Sun, 11, 11),
+        ("1-16周 星期天 第1-4节 主楼B-204", Weekday::Sun, 1, 4),
+        ("1-16周 星期6 3-4节 主楼B-204", Weekday::Sat, 3, 4),
+        ("1-16周 星期四 第1,2节 主楼B-204", Weekday::Thu, 1, 2),
+        ("星期二 第7-8节 1-16周 主楼B-204", Weekday::Tue, 7, 8),
+    ];
+    for (text, weekday, first, last) in cases {
+        let meeting = only_meeting(text);
+        assert_eq!(meeting.weekday, *weekday, "{text}");
+        assert_eq!((meeting.first_period, meeting.last_period), (*first, *last));
+        assert_eq!(meeting.location().as_deref(), Some("主楼B-204"), "{text}");
+    }
+}
+
+#[test]
+fn without_spaces() {
+    let meeting = only_meeting("1-16周星期一第1-2节主楼A-101");
+    assert_eq!(meeting.weekday, Weekday::Mon);
+    assert_eq!(meeting.location().as_deref(), Some("主楼A-101"));
+}
+
+#[test]
+fn locations() {
+    // 原文、校区、教学楼、教室
+    type Case<'a> = (&'a str, Option<&'a str>, Option<&'a str>, Option<&'a str>);
+    let cases: &[Case] = &[
+        (
+            "1-16周 星期一 第1-2节 兴庆校区 主楼A-101",
+            Some("兴庆校区"),
+            Some("主楼A"),
+            Some("101"),
+        ),
+        (
+            "1-16周 星期一 第1-2节 主楼A-101(兴庆校区)",
+            Some("兴庆校区"),
+            Some("主楼A"),
+            Some("101"),
+        ),
+        (
+            "1-16周 星期一 第1-2节 兴庆校区主楼A-101",
+            Some("兴庆校区"),
+            Some("主楼A"),
+            Some("101"),
+        ),
+        (
+            "1-16周 星期一 第1-2节 创新港 涵英楼-2-201",
+            Some("创新港"),
+            Some("涵英楼-2"),
+            Some("201"),
+        ),
+        (
+            "1-16周 星期一 第1-2节 西二楼203",
+            None,
+            Some("西二楼"),
+            Some("203"),
+        ),
+        ("1-16周 星期一 第1-2节 体育场", None, Some("体育场"), None),
+        (
+            "1-16周 星期一 第1-2节 雁塔校区",
+            Some("雁塔校区"),
+            None,
+            None,
+        ),
+        ("1-16周 星期一 第1-2节", None, None, None),
+    ];
+    for (text, campus, building, room) in cases {
+        let meeting = only_meeting(text);
+        assert_eq!(meeting.campus.as_deref(), *campus, "{text}");
+        assert_eq!(meeting.building.as_deref(), *building, "{text}");
+        assert_eq!(meeting.room.as_deref(), *room, "{text}");
+    }
+}
+
+#[test]
+fn multiple_meetings() {
+    for text in [
+        "1-16周 星期二 第5-6节 主楼B-204,1-16周 星期四 第1-2节 主楼B-204",
+        "1-16周 星期二 第5-6节 主楼B-204；1-16周 星期四 第1-2节 主楼B-204",
+        "1-16周 星期二 第5-6节 主楼B-204\n1-16周 星期四 第1-2节 主楼B-204",
+    ] {
+        let schedule = parse_teaching_place(text);
+        assert!(schedule.unparsed.is_empty(), "{text}");
+        let days: Vec<Weekday> = schedule.meetings.iter().map(|m| m.weekday).collect();
+        assert_eq!(days, vec![Weekday::Tue, Weekday::Thu], "{text}");
+    }
+
+    // 纯数字的教室号后面紧跟下一次上课的周次
+    let schedule = parse_teaching_place("1-16周 星期二 第5-6节 3-301,1-16周 星期四 第1-2节 3-301");
+    assert!(schedule.unparsed.is_empty());
+    assert_eq!(schedule.meetings.len(), 2);
+    for (meeting, (weekday, first)) in schedule
+        .meetings
+        .iter()
+        .zip([(Weekday::Tue, 5), (Weekday::Thu, 1)])
+    {
+        assert_eq!(meeting.weeks, Weeks::range(1, 16, Parity::All));
+        assert_eq!((meeting.weekday, meeting.first_period), (weekday, first));
+        assert_eq!(meeting.building.as_deref(), Some("3"));
+        assert_eq!(meeting.room.as_deref(), Some("301"));
+    }
+    let schedule = parse_teaching_place("1-16周 星期二 第5-6节 3-301,第1-2节 星期四 1-16周");
+    assert_eq!(schedule.meetings.len(), 2);
+    assert_eq!(schedule.meetings[1].weekday, Weekday::Thu);
+
+    let schedule =
+        parse_teaching_place("1-4,6-8周 星期一 第1-2节 中2-1201,1-8周 星期三 第3-4节 中2-1201");
+    assert_eq!(schedule.meetings.len(), 2);
+    assert_eq!(schedule.meetings[0].weeks, weeks(&[1, 2, 3, 4, 6, 7, 8]));
+    assert_eq!(schedule.meetings[1].weeks, Weeks::range(1, 8, Parity::All));
+}
+
+#[test]
+fn unparsed_text_is_kept() {
+    for text in [
+        "待定",
+        "网络课程",
+        "星期一 第1-2节 主楼A-101",
+        "1-16周 主楼A-101",
+        "1-16周 星期二 第5-6节 星期四 第1-2节 主楼A-101",
+        "1-16周 星期二 第5-6节 第7-8节 主楼A-101",
+    ] {
+        let schedule = parse_teaching_place(text);
+        assert!(schedule.meetings.is_empty(), "{text}");
+        assert_eq!(schedule.unparsed, vec![text.to_string()]);
+    }
+    assert_eq!(parse_teaching_place("").meetings.len(), 0);
+    assert!(parse_teaching_place("  ").unparsed.is_empty());
+
+    let schedule = parse_teaching_place("1-16周 星期一 第1-2节 主楼A-101,另行通知");
+    assert_eq!(schedule.meetings.len(), 1);
+    assert_eq!(schedule.unparsed, vec!["另行通知".to_string()]);
+}
+
+#[test]
+fn weeks_display() {
+    assert_eq!(Weeks::range(1, 16, Parity::All).to_string(), "1-16周");
+    assert_eq!(Weeks::range(1, 15, Parity::Odd).to_string(), "1-15周(单)");
+    assert_eq!(Weeks::range(2, 16, Parity::Even).to_string(), "2-16周(双)");
+    assert_eq!(weeks(&[1, 2, 3, 5, 8, 9]).to_string(), "1-3,5,8-9周");
+    assert_eq!(Weeks::range(60, 70, Parity::All).len(), 4);
+}
+
+#[test]
+fn teaching_class_schedule() {
+    let schedule = class("A", "1-16周 星期一 第1-2节 主楼A-101")
+        .build()
+        .schedule();
+    assert_eq!(schedule.meetings.len(), 1);
+    assert_eq!(schedule.meetings[0].room.as_deref(), Some("101"));
+}
+
+#[test]
+fn reports_pairwise_conflicts() {
+    let classes = [
+        class("A", "1-16周 星期一 第1-2节 主楼A-101").build(),
+        class("B", "1-8周 星期一 第2-3节 主楼A-102").build(),
+        // 与 A 同一时间，但只在双周上课，而 A 全程上课
+        class("C", "2-16周(双) 星期一 第1-2节 主楼A-103").build(),
+        // 与 B 节次重叠，但周次不重叠
+        class("D", "9-16周 星期一 第3-4节 主楼A-104").build(),
+        class("E", "1-16周 星期二 第1-2节 主楼A-105").build(),
+    ];
+    let timetable: Timetable = classes.iter().collect();
+    let conflicts = timetable.conflicts();
+    let pairs: Vec<(&str, &str)> = conflicts
+        .iter()
+        .map(|c| (c.first.as_str(), c.second.as_str()))
+        .collect();
+    assert_eq!(pairs, vec![("A", "B"), ("A", "C"), ("B", "C")]);
+
+    let overlap = &conflicts[0].overlaps[0];
+    assert_eq!(overlap.weeks, Weeks::range(1, 8, Parity::All));
+    assert_eq!(overlap.weekday, Weekday::Mon);
+    assert_eq!((overlap.first_period, overlap.last_period), (2, 2));
+    assert_eq!(overlap.to_string(), "1-8周 星期一 第2节");
+    assert_eq!(
+        conflicts[1].overlaps[0].weeks,
+        Weeks::range(2, 16, Parity::Even)
+    );
+    assert_eq!(
+        conflicts[2].overlaps[0].to_string(),
+        "2-8周(双) 星期一 第2节"
+    );
+}
+
+#[test]
+fn checks_a_candidate_against_the_timetable() {
+    let mut timetable = Timetable::new();
+    timetable.add_class(&class("A", "1-16周 星期三 第3-4节 主楼A-101").build());
+    timetable.add("B", parse_teaching_place("待定"));
+
+    let candidate = parse_teaching_place("1-15周(单) 星期三 第4-5节 中2-3205");
+    let conflicts = timetable.conflicts_with("X", &candidate);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].first, "A");
+    assert_eq!(conflicts[0].overlaps[0].weeks.len(), 8);
+    assert!(candidate.conflicts_with(&parse_teaching_place("3周 星期三 第5节 主楼")));
+    assert!(!candidate.conflicts_with(&parse_teaching_place("4周 星期三 第5节 主楼")));
+
+    // 无法解析的教学班不参与冲突检查，但会被列出
+    assert_eq!(timetable.unparsed(), vec![("B", &["待定".to_string()][..])]);
+}
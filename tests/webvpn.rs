@@ -0,0 +1,133 @@
+mod common;
+
+use common::{login_course, options};
+use xjtu_login::course::{CourseSession, CourseType};
+use xjtu_login::endpoints::Endpoints;
+use xjtu_login::login::LoginOptions;
+use xjtu_login::mock::{MockClass, MockServer, Scenario};
+use xjtu_login::webvpn::{
+    AccessMode, WEBVPN_ORIGIN, decrypt_host, encrypt_host, rewrite_url_with_origin,
+};
+
+/// 固定的 IV `wrdvpnisthebest!` 的十六进制
+const IV_HEX: &str = "77726476706e69737468656265737421";
+
+#[test]
+fn encrypts_known_hosts() {
+    // 期望值由 `openssl enc -aes-128-cfb -nopad` 独立算出
+    for (host, cipher) in [
+        ("xkfw.xjtu.edu.cn", "e8fc478b692862446b468ca88d1b203b"),
+        ("login.xjtu.edu.cn", "fcf84695297e705a6a1dc7a99c406d3655"),
+        ("127.0.0.1", "a1a516d2777e381e2f"),
+    ] {
+        assert_eq!(encrypt_host(host), format!("{IV_HEX}{cipher}"), "{host}");
+    }
+}
+
+#[test]
+fn rewrites_urls_onto_the_gateway() {
+    let xkfw = format!("{IV_HEX}e8fc478b692862446b468ca88d1b203b");
+    assert_eq!(
+        rewrite_url_with_origin(
+            WEBVPN_ORIGIN,
+            "https://xkfw.xjtu.edu.cn/xsxkapp/sys/xsxkapp/index.do?a=1&b=%E4%B8%AD"
+        ),
+        format!(
+            "https://webvpn.xjtu.edu.cn/https/{xkfw}/xsxkapp/sys/xsxkapp/index.do?a=1&b=%E4%B8%AD"
+        )
+    );
+    // 带端口时协议段为 `scheme-port`
+    assert_eq!(
+        rewrite_url_with_origin(WEBVPN_ORIGIN, "http://xkfw.xjtu.edu.cn:8080/"),
+        format!("https://webvpn.xjtu.edu.cn/http-8080/{xkfw}/")
+    );
+    assert_eq!(
+        rewrite_url_with_origin("http://127.0.0.1:4000", "http://127.0.0.1/cas/login"),
+        format!("http://127.0.0.1:4000/http/{IV_HEX}a1a516d2777e381e2f/cas/login")
+    );
+}
+
+#[test]
+fn leaves_gateway_and_unparseable_urls_alone() {
+    for url in [
+        "https://webvpn.xjtu.edu.cn/login?cas_login=true",
+        "not a url",
+        "/relative/path",
+        "data:text/plain,hello",
+    ] {
+        assert_eq!(rewrite_url_with_origin(WEBVPN_ORIGIN, url), url);
+    }
+}
+
+#[test]
+fn decrypts_what_it_encrypts() {
+    for host in ["xkfw.xjtu.edu.cn", "127.0.0.1", ""] {
+        assert_eq!(decrypt_host(&encrypt_host(host)).as_deref(), Some(host));
+    }
+    assert_eq!(
+        decrypt_host(&format!("{IV_HEX}e8fc478b692862446b468ca88d1b203b")).as_deref(),
+        Some("xkfw.xjtu.edu.cn")
+    );
+    for bad in ["", "7772", "zz", "77726476706e69737468656265737421e"] {
+        assert_eq!(decrypt_host(bad), None, "{bad}");
+    }
+}
+
+async fn start() -> MockServer {
+    MockServer::start(Scenario {
+        classes: vec![MockClass::new("TC-1", "ECON2001", "国际结算", 30)],
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+/// 经由网关登录选课系统并查询课程
+async fn list_via_gateway(server: &MockServer, options: &LoginOptions<'_>) {
+    let login = login_course(server, options).await;
+    assert!(login.webvpn);
+    let session = CourseSession::from_login(&login).await.unwrap();
+    let batch = &session.get_batch_list().await.unwrap()[0];
+    let courses = session
+        .list_course(batch, CourseType::TJKC, 0, "")
+        .await
+        .unwrap();
+    assert_eq!(courses[0].course_number, "ECON2001");
+    // 网关和经由网关的选课系统各提交一次密码
+    assert_eq!(server.form_logins(), 2);
+    assert!(server.gateway_requests() > 0);
+}
+
+#[tokio::test]
+async fn logs_in_through_gateway() {
+    let server = start().await;
+    let options = LoginOptions {
+        mode: AccessMode::WebVpn,
+        ..options(&server)
+    };
+    list_via_gateway(&server, &options).await;
+}
+
+#[tokio::test]
+async fn auto_falls_back_to_gateway() {
+    let server = start().await;
+    // 直接访问选课系统不可达，网关仍能访问
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let unreachable = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let fallback = LoginOptions {
+        mode: AccessMode::Auto,
+        endpoints: Endpoints {
+            course: unreachable,
+            ..server.endpoints()
+        },
+        ..Default::default()
+    };
+    list_via_gateway(&server, &fallback).await;
+
+    // 可以直接访问时不经过网关
+    let server = start().await;
+    let login = login_course(&server, &options(&server)).await;
+    assert!(!login.webvpn);
+    assert_eq!(server.gateway_requests(), 0);
+}